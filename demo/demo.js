@@ -18,6 +18,57 @@ let putStr = function (module, str) {
 };
 // /copying strings
 
+// event payloads, see src/payload.rs for the format
+let PayloadWriter = class {
+    constructor() {
+        this.parts = [];
+        this.len = 0;
+    }
+
+    bytes(bytes) {
+        this.parts.push(bytes);
+        this.len += bytes.length;
+        return this;
+    }
+
+    number(size, write) {
+        let view = new DataView(new ArrayBuffer(size));
+        write(view);
+        return this.bytes(new Uint8Array(view.buffer));
+    }
+
+    u32(value) { return this.number(4, (view) => view.setUint32(0, value >>> 0, true)); }
+    i32(value) { return this.number(4, (view) => view.setInt32(0, value | 0, true)); }
+    f64(value) { return this.number(8, (view) => view.setFloat64(0, value, true)); }
+
+    byteArray(bytes) {
+        return this.u32(bytes.length).bytes(bytes);
+    }
+
+    string(str) {
+        return this.byteArray(utfEncoder.encode(str));
+    }
+
+    list(items, write) {
+        let array = Array.from(items);
+        this.u32(array.length);
+        for (let item of array) { write(this, item); }
+        return this;
+    }
+
+    // the game takes ownership of the buffer and frees it
+    put(module) {
+        let ptr = module.alloc(this.len);
+        let slice = new Uint8Array(module.memory.buffer, ptr, this.len);
+        let offset = 0;
+        for (let part of this.parts) {
+            slice.set(part, offset);
+            offset += part.length;
+        }
+        return {ptr: ptr, len: this.len};
+    }
+};
+
 let io = {
     puts: (ptr, len) => console.log(getStr(Module, ptr, len)),
     alert: (n) => alert(n),
@@ -35,15 +86,76 @@ let eventLoop = function(Module) {
     const EVENT_MOUSE_MOVE = 2;
     const EVENT_KEY_DOWN = 3;
     const EVENT_KEY_UP = 4;
+    const EVENT_GAMEPAD = 5;
+    const EVENT_MOUSE_DOWN = 6;
+    const EVENT_MOUSE_UP = 7;
+    const EVENT_WHEEL = 8;
+    const EVENT_TOUCH_START = 9;
+    const EVENT_TOUCH_MOVE = 10;
+    const EVENT_TOUCH_END = 11;
+    const EVENT_TIMER = 12;
+    const EVENT_VISIBILITY_CHANGE = 13;
+    const EVENT_FOCUS = 14;
+    const EVENT_BLUR = 15;
+    const EVENT_RESIZE = 16;
+    const EVENT_POINTER_DOWN = 17;
+    const EVENT_POINTER_MOVE = 18;
+    const EVENT_POINTER_UP = 19;
+    const EVENT_TEXT_INPUT = 20;
+    const EVENT_MESSAGE = 21;
+    // event categories, same as `EVENTS_*` in eventloop.rs
+    const EVENTS_MOUSE_MOVE = 1;
+    const EVENTS_MOUSE_BUTTONS = 2;
+    const EVENTS_WHEEL = 4;
+    const EVENTS_KEYBOARD = 8;
+    const EVENTS_TOUCH = 16;
+    const EVENTS_POINTER = 32;
+    const EVENTS_PAGE = 64;
+    const EVENTS_TEXT = 128;
+    const EVENTS_MESSAGE = 256;
+    const POINTER_KINDS = {mouse: 0, pen: 1, touch: 2};
     let eventLoopsDict = new Map();
     eventLoopsDict.counter = 0;
 
     let keyEventFlags = function(event) {
-        return (event.shiftKey ? 1 : 0) | (event.ctrlKey ? 2 : 0) | (event.altKey ? 4 : 0);
+        return (event.shiftKey ? 1 : 0) | (event.ctrlKey ? 2 : 0) | (event.altKey ? 4 : 0)
+            | (event.repeat ? 8 : 0) | (event.metaKey ? 16 : 0);
+    };
+    let keyPayload = function(event) {
+        return new PayloadWriter().string(event.code).string(event.key).put(Module);
+    };
+    // pointer coordinates are sent relative to the target element, in CSS pixels,
+    // the SVG viewBox is kept the same as its size, see resizeCb
+    let targetPos = function(target, event) {
+        let rect = target.getBoundingClientRect();
+        return {
+            x: Math.round(event.clientX - rect.left),
+            y: Math.round(event.clientY - rect.top),
+        };
+    };
+    const WHEEL_LINE = 16;
+    const WHEEL_PAGE = 800;
+    let wheelScale = function(event) {
+        switch (event.deltaMode) {
+            case WheelEvent.DOM_DELTA_LINE: return WHEEL_LINE;
+            case WheelEvent.DOM_DELTA_PAGE: return WHEEL_PAGE;
+            default: return 1;
+        }
+    };
+    let gamepadPayload = function(index, pad) {
+        let writer = new PayloadWriter().u32(index);
+        if (!pad) {
+            return writer.u32(0).list([], null).list([], null).put(Module);
+        }
+        return writer.u32(packButtons(pad))
+            .list(pad.axes, (w, value) => w.f64(value))
+            .list(pad.buttons, (w, button) => w.f64(button.value))
+            .put(Module);
     };
-    let charKey = function(event) {
-        if (event.key.length !== 1) { return 0xffffffff; }
-        return event.key.charCodeAt(0);
+    let packButtons = function(pad) {
+        return pad.buttons.reduce((packed, button, i) => {
+            return (button.pressed && i < 32) ? (packed | (1 << i)) : packed;
+        }, 0) >>> 0;
     };
 
     let EventLoop = class {
@@ -52,37 +164,179 @@ let eventLoop = function(Module) {
             let id = ++eventLoopsDict.counter;
             eventLoopsDict.set(id, self);
             self.id = id;
+            self.callback = callback;
             self.dead = false;
+            // events are listened for on this element, see `listeners`
+            self.target = document.querySelector('svg');
+            self.subscriptions = 0;
             self.rafId = null;
+            self.gamepads = new Set();
+            // timer handle -> browser timer id
+            self.timers = new Map();
+            self.timerCounter = 0;
+
+            self.pollGamepads = function(time) {
+                if (!navigator.getGamepads) { return; }
+                let seen = new Set();
+                for (let pad of navigator.getGamepads()) {
+                    if (!pad || !pad.connected) { continue; }
+                    seen.add(pad.index);
+                    let payload = gamepadPayload(pad.index, pad);
+                    callback(id, EVENT_GAMEPAD, payload.ptr, payload.len, 0, time);
+                }
+                // release everything on disconnected gamepads
+                for (let index of self.gamepads) {
+                    if (seen.has(index)) { continue; }
+                    let payload = gamepadPayload(index, null);
+                    callback(id, EVENT_GAMEPAD, payload.ptr, payload.len, 0, time);
+                }
+                self.gamepads = seen;
+            };
 
-            self.rafCb = function() {
+            self.rafCb = function(time) {
                 self.rafId = null;
                 if (self.dead) { return; }
-                callback(id, EVENT_ANIMATION_FRAME, 0, 0, 0);
+                self.pollGamepads(time);
+                callback(id, EVENT_ANIMATION_FRAME, 0, 0, 0, time);
             };
 
             self.mouseMoveCb = function(event) {
                 if (self.dead) { return; }
-                callback(id, EVENT_MOUSE_MOVE, event.pageX, event.pageY, 0)
+                let pos = targetPos(self.target, event);
+                callback(id, EVENT_MOUSE_MOVE, pos.x, pos.y, 0, event.timeStamp);
             };
 
+            self.mouseDownCb = function(event) {
+                if (self.dead) { return; }
+                let pos = targetPos(self.target, event);
+                callback(id, EVENT_MOUSE_DOWN, pos.x, pos.y, event.button, event.timeStamp);
+            };
+
+            self.mouseUpCb = function(event) {
+                if (self.dead) { return; }
+                let pos = targetPos(self.target, event);
+                callback(id, EVENT_MOUSE_UP, pos.x, pos.y, event.button, event.timeStamp);
+            };
+
+            self.wheelCb = function(event) {
+                if (self.dead) { return; }
+                let scale = wheelScale(event);
+                callback(id, EVENT_WHEEL, Math.round(event.deltaX * scale), Math.round(event.deltaY * scale), 0, event.timeStamp);
+            };
+
+            let touchCb = function(msg) {
+                return function(event) {
+                    if (self.dead) { return; }
+                    // keep the page from scrolling and zooming
+                    event.preventDefault();
+                    let payload = new PayloadWriter().list(event.changedTouches, (w, touch) => {
+                        let pos = targetPos(self.target, touch);
+                        w.u32(touch.identifier).i32(pos.x).i32(pos.y);
+                    }).put(Module);
+                    callback(id, msg, payload.ptr, payload.len, 0, event.timeStamp);
+                };
+            };
+            self.touchStartCb = touchCb(EVENT_TOUCH_START);
+            self.touchMoveCb = touchCb(EVENT_TOUCH_MOVE);
+            self.touchEndCb = touchCb(EVENT_TOUCH_END);
+
+            // the right mouse button is used for thrust
+            self.contextMenuCb = function(event) {
+                event.preventDefault();
+            };
+            // keep getting mouse events when a button is released outside of the target
+            self.captureCb = function(event) {
+                if (event.pointerType === 'mouse') {
+                    self.target.setPointerCapture(event.pointerId);
+                }
+            };
+
+            let pointerCb = function(msg) {
+                return function(event) {
+                    if (self.dead) { return; }
+                    let pos = targetPos(self.target, event);
+                    // the pointer kind is sent in the top byte of the id
+                    let kind = POINTER_KINDS[event.pointerType] || 0;
+                    let packed = ((event.pointerId & 0xffffff) | (kind << 24)) >>> 0;
+                    callback(id, msg, packed, pos.x, pos.y, event.timeStamp);
+                };
+            };
+            self.pointerDownCb = pointerCb(EVENT_POINTER_DOWN);
+            self.pointerMoveCb = pointerCb(EVENT_POINTER_MOVE);
+            self.pointerUpCb = pointerCb(EVENT_POINTER_UP);
+
             self.keyDown = function(event) {
                 if (self.dead) { return; }
-                callback(id, EVENT_KEY_DOWN, event.which, charKey(event), keyEventFlags(event));
+                let payload = keyPayload(event);
+                callback(id, EVENT_KEY_DOWN, payload.ptr, payload.len, keyEventFlags(event), event.timeStamp);
             };
 
             self.keyUp = function(event) {
                 if (self.dead) { return; }
-                callback(id, EVENT_KEY_UP, event.which, charKey(event), keyEventFlags(event));
+                let payload = keyPayload(event);
+                callback(id, EVENT_KEY_UP, payload.ptr, payload.len, keyEventFlags(event), event.timeStamp);
+            };
+
+            let sendText = function(text, time) {
+                let payload = new PayloadWriter().string(text).put(Module);
+                callback(id, EVENT_TEXT_INPUT, payload.ptr, payload.len, 0, time);
+            };
+            // keys that type a single character, without shortcuts like ctrl+c
+            self.textKeyCb = function(event) {
+                if (self.dead || event.ctrlKey || event.metaKey) { return; }
+                if (Array.from(event.key).length !== 1) { return; }
+                sendText(event.key, event.timeStamp);
+            };
+            self.pasteCb = function(event) {
+                if (self.dead) { return; }
+                let text = event.clipboardData ? event.clipboardData.getData('text') : '';
+                if (text) { sendText(text, event.timeStamp); }
+            };
+
+            // `postMessage({channel, data})` from the same origin, `data` is a string or bytes
+            self.messageCb = function(event) {
+                if (self.dead || event.origin !== location.origin) { return; }
+                let message = event.data;
+                if (!message || typeof message.channel !== 'string') { return; }
+                let data = typeof message.data === 'string'
+                    ? utfEncoder.encode(message.data)
+                    : new Uint8Array(message.data || []);
+                let payload = new PayloadWriter().string(message.channel).byteArray(data).put(Module);
+                callback(id, EVENT_MESSAGE, payload.ptr, payload.len, 0, event.timeStamp);
             };
 
-            self.subscribeMouse();
-            self.subscribeKeyboard();
+            self.visibilityCb = function() {
+                if (self.dead) { return; }
+                let visible = document.visibilityState !== 'hidden';
+                callback(id, EVENT_VISIBILITY_CHANGE, visible ? 1 : 0, 0, 0, performance.now());
+            };
+            self.focusCb = function(event) {
+                if (self.dead) { return; }
+                callback(id, EVENT_FOCUS, 0, 0, 0, event.timeStamp);
+            };
+            self.blurCb = function(event) {
+                if (self.dead) { return; }
+                callback(id, EVENT_BLUR, 0, 0, 0, event.timeStamp);
+            };
+            // the device pixel ratio is sent in thousandths
+            self.resizeCb = function() {
+                if (self.dead) { return; }
+                let rect = self.target.getBoundingClientRect();
+                let width = Math.round(rect.width);
+                let height = Math.round(rect.height);
+                // a canvas target sizes itself
+                if (self.target instanceof SVGSVGElement) {
+                    self.target.setAttribute('viewBox', '0 0 ' + width + ' ' + height);
+                }
+                let dpr = Math.round((window.devicePixelRatio || 1) * 1000);
+                callback(id, EVENT_RESIZE, width, height, dpr, performance.now());
+            };
         }
 
         raf() {
             let self = this;
-            if (self.dead) { return; }
+            // several handlers may ask for the same frame
+            if (self.dead || self.rafId) { return; }
             self.rafId = requestAnimationFrame(self.rafCb);
         }
 
@@ -91,28 +345,102 @@ let eventLoop = function(Module) {
             if (self.rafId) { cancelAnimationFrame(self.rafId); }
         }
 
-        subscribeMouse() {
+        setTimer(delay, repeat) {
             let self = this;
-            if (self.dead) { return; }
-            window.addEventListener('mousemove', self.mouseMoveCb);
+            if (self.dead) { return 0; }
+            let handle = ++self.timerCounter;
+            let fire = () => {
+                if (!repeat) { self.timers.delete(handle); }
+                if (self.dead) { return; }
+                self.callback(self.id, EVENT_TIMER, handle, 0, 0, performance.now());
+            };
+            self.timers.set(handle, repeat ? setInterval(fire, delay) : setTimeout(fire, delay));
+            return handle;
+        }
+
+        clearTimer(handle) {
+            let self = this;
+            if (!self.timers.has(handle)) { return false; }
+            // clearTimeout and clearInterval are interchangeable
+            clearTimeout(self.timers.get(handle));
+            self.timers.delete(handle);
+            return true;
+        }
+
+        // [category, event target, event type, callback, options]
+        listeners() {
+            let self = this;
+            let el = self.target;
+            let active = {passive: false};
+            return [
+                [EVENTS_MOUSE_MOVE, el, 'mousemove', self.mouseMoveCb],
+                [EVENTS_MOUSE_BUTTONS, el, 'mousedown', self.mouseDownCb],
+                [EVENTS_MOUSE_BUTTONS, el, 'mouseup', self.mouseUpCb],
+                [EVENTS_MOUSE_BUTTONS, el, 'contextmenu', self.contextMenuCb],
+                [EVENTS_MOUSE_BUTTONS, el, 'pointerdown', self.captureCb],
+                [EVENTS_WHEEL, el, 'wheel', self.wheelCb],
+                [EVENTS_KEYBOARD, el, 'keydown', self.keyDown],
+                [EVENTS_KEYBOARD, el, 'keyup', self.keyUp],
+                [EVENTS_TOUCH, el, 'touchstart', self.touchStartCb, active],
+                [EVENTS_TOUCH, el, 'touchmove', self.touchMoveCb, active],
+                [EVENTS_TOUCH, el, 'touchend', self.touchEndCb, active],
+                [EVENTS_TOUCH, el, 'touchcancel', self.touchEndCb, active],
+                [EVENTS_POINTER, el, 'pointerdown', self.pointerDownCb],
+                [EVENTS_POINTER, el, 'pointermove', self.pointerMoveCb],
+                [EVENTS_POINTER, el, 'pointerup', self.pointerUpCb],
+                [EVENTS_POINTER, el, 'pointercancel', self.pointerUpCb],
+                [EVENTS_PAGE, document, 'visibilitychange', self.visibilityCb],
+                [EVENTS_PAGE, el, 'focus', self.focusCb],
+                [EVENTS_PAGE, el, 'blur', self.blurCb],
+                [EVENTS_PAGE, window, 'resize', self.resizeCb],
+                [EVENTS_TEXT, el, 'keydown', self.textKeyCb],
+                [EVENTS_TEXT, el, 'paste', self.pasteCb],
+                [EVENTS_MESSAGE, window, 'message', self.messageCb],
+            ];
         }
 
-        unsubscribeMouse() {
+        listen(categories, add) {
             let self = this;
-            window.removeEventListener('mousemove', self.mouseMoveCb);
+            for (let [category, target, type, cb, options] of self.listeners()) {
+                if (!(categories & category)) { continue; }
+                if (add) {
+                    target.addEventListener(type, cb, options);
+                } else {
+                    target.removeEventListener(type, cb, options);
+                }
+            }
         }
 
-        subscribeKeyboard() {
+        subscribe(categories) {
             let self = this;
             if (self.dead) { return; }
-            window.addEventListener('keydown', self.keyDown);
-            window.addEventListener('keyup', self.keyUp);
+            let added = categories & ~self.subscriptions;
+            self.subscriptions |= added;
+            self.listen(added, true);
+            // key events only go to focusable elements
+            if ((added & (EVENTS_KEYBOARD | EVENTS_TEXT)) && !self.target.hasAttribute('tabindex')) {
+                self.target.setAttribute('tabindex', '0');
+            }
+            // the game learns the target size once its handler is registered
+            if (added & EVENTS_PAGE) { setTimeout(self.resizeCb, 0); }
+        }
+
+        unsubscribe(categories) {
+            let self = this;
+            let removed = categories & self.subscriptions;
+            self.listen(removed, false);
+            self.subscriptions &= ~removed;
         }
 
-        unsubscribeKeyboard() {
+        setTarget(selector) {
             let self = this;
-            window.removeEventListener('keydown', self.keyDown);
-            window.removeEventListener('keyup', self.keyUp);
+            let target = document.querySelector(selector);
+            if (self.dead || !target) { return false; }
+            let subscriptions = self.subscriptions;
+            self.unsubscribe(subscriptions);
+            self.target = target;
+            self.subscribe(subscriptions);
+            return true;
         }
 
         shutdown() {
@@ -121,8 +449,8 @@ let eventLoop = function(Module) {
             self.dead = true;
 
             self.caf();
-            self.unsubscribeMouse();
-            self.unsubscribeKeyboard();
+            for (let handle of Array.from(self.timers.keys())) { self.clearTimer(handle); }
+            self.unsubscribe(self.subscriptions);
 
             eventLoopsDict.delete(self.id);
         }
@@ -133,10 +461,31 @@ let eventLoop = function(Module) {
         eventLoopsDict.get(id).raf();
         return true;
     };
+    let setTimer = function(id, delay, repeat) {
+        if (!eventLoopsDict.has(id)) { return 0; }
+        return eventLoopsDict.get(id).setTimer(delay, repeat);
+    };
+    let clearTimer = function(id, handle) {
+        if (!eventLoopsDict.has(id)) { return false; }
+        return eventLoopsDict.get(id).clearTimer(handle);
+    };
+    let subscribe = function(id, categories) {
+        if (!eventLoopsDict.has(id)) { return; }
+        eventLoopsDict.get(id).subscribe(categories);
+    };
+    let unsubscribe = function(id, categories) {
+        if (!eventLoopsDict.has(id)) { return; }
+        eventLoopsDict.get(id).unsubscribe(categories);
+    };
+    let setTarget = function(id, selectorPtr, selectorLen) {
+        if (!eventLoopsDict.has(id)) { return false; }
+        return eventLoopsDict.get(id).setTarget(getStr(Module, selectorPtr, selectorLen));
+    };
     let destroy = function(id) {
         if (!eventLoopsDict.has(id)) { return false; }
-        eventLoopsDict.get(id).destroy();
-        setInterval(() => Module.event_loop_cb(id, EVENT_DESTROYED, 0, 0, 0), 0);
+        eventLoopsDict.get(id).shutdown();
+        // the loop may be shut down from its own callback, so it's told later
+        setTimeout(() => Module.event_loop_cb(id, EVENT_DESTROYED, 0, 0, 0, performance.now()), 0);
         return true;
     };
 
@@ -144,11 +493,287 @@ let eventLoop = function(Module) {
         event_loop_new: () => new EventLoop(Module.event_loop_cb).id,
         event_loop_raf: raf,
         event_loop_shutdown: destroy,
+        event_loop_set_timer: setTimer,
+        event_loop_clear_timer: clearTimer,
+        event_loop_subscribe: subscribe,
+        event_loop_unsubscribe: unsubscribe,
+        event_loop_set_target: setTarget,
+    };
+};
+
+let gamepad = {
+    gamepad_rumble: (strong, weak, duration) => {
+        if (!navigator.getGamepads) { return; }
+        for (let pad of navigator.getGamepads()) {
+            if (!pad || !pad.vibrationActuator) { continue; }
+            pad.vibrationActuator.playEffect('dual-rumble', {
+                duration: duration,
+                strongMagnitude: strong,
+                weakMagnitude: weak,
+            }).catch(() => {});
+        }
+    },
+};
+
+let storage = {
+    storage_get: (keyPtr, keyLen, lenPtr) => {
+        let value = null;
+        try {
+            value = window.localStorage.getItem(getStr(Module, keyPtr, keyLen));
+        } catch (e) {
+            // storage may be disabled
+        }
+        if (value === null) { return 0; }
+        let str = putStr(Module, value);
+        new Uint32Array(Module.memory.buffer, lenPtr, 1)[0] = str.len;
+        return str.ptr;
+    },
+    storage_set: (keyPtr, keyLen, valPtr, valLen) => {
+        try {
+            window.localStorage.setItem(getStr(Module, keyPtr, keyLen), getStr(Module, valPtr, valLen));
+        } catch (e) {
+            // storage may be disabled
+        }
+    },
+};
+
+// the game only gets keys while its SVG element is focused
+let focusGame = function() {
+    document.querySelector('svg').focus();
+};
+
+let gameUi = function(Module) {
+    let button = function(id, onclick) {
+        document.getElementById(id).addEventListener('click', (event) => {
+            // keys go to the game, and Space doesn't click the button again
+            focusGame();
+            onclick();
+        });
+    };
+    button('game-stop', () => Module.exports.stop_game());
+    // starting a game stops the running one
+    button('game-start', () => Module.exports.my_main());
+    // deterministic, see `start_deterministic_game`
+    button('game-start-seeded', () => {
+        Module.exports.start_deterministic_game(Number(document.getElementById('game-seed').value) >>> 0);
+    });
+    button('game-checksum', () => {
+        // wasm returns the u64 as a signed BigInt
+        let checksum = BigInt.asUintN(64, Module.exports.game_checksum());
+        document.getElementById('checksum').textContent = checksum.toString(16).padStart(16, '0');
+    });
+    button('svg-export', () => {
+        let len = Module.exports.svg_export_frame();
+        if (len === 0) { return; }
+        let svg = getStr(Module, Module.exports.svg_export_ptr(), len);
+        let link = document.createElement('a');
+        link.href = URL.createObjectURL(new Blob([svg], { type: 'image/svg+xml' }));
+        link.download = 'asteroids-frame.svg';
+        link.click();
+        URL.revokeObjectURL(link.href);
+    });
+};
+
+let keybindsUi = function(Module) {
+    // in InputIndex and InputContext order
+    const ACTIONS = [
+        'Shoot', 'Thrust', 'Reverse', 'Rotate left', 'Rotate right', 'Pause',
+        'Confirm', 'Resume', 'Submit', 'Cancel', 'Erase',
+    ];
+    const CONTEXTS = ['Gameplay', 'Menu', 'Text entry', 'Paused'];
+    // names in the player's keyboard layout, as far as the game knows it
+    let keyName = (code) => getStr(Module,
+        Module.exports.key_name_ptr(code), Module.exports.key_name_len(code));
+
+    let table = document.getElementById('keybinds-table');
+    let status = document.getElementById('keybinds-status');
+
+    let button = function(text, onclick) {
+        let button = document.createElement('button');
+        button.textContent = text;
+        button.addEventListener('click', (event) => {
+            // keys go to the game, and Space doesn't click the button again
+            focusGame();
+            onclick();
+            update();
+        });
+        return button;
     };
+
+    let update = function() {
+        table.textContent = '';
+        ACTIONS.forEach((name, action) => {
+            let row = table.insertRow();
+            row.insertCell().textContent = CONTEXTS[Module.exports.action_context(action)];
+            row.insertCell().textContent = name;
+            let keys = row.insertCell();
+            let count = Module.exports.keybinds_count(action);
+            for (let i = 0; i < count; i++) {
+                let code = Module.exports.keybinds_get(action, i);
+                keys.appendChild(button(keyName(code) + ' \u00d7',
+                    () => Module.exports.keybinds_unbind(action, code)));
+            }
+            row.insertCell().appendChild(button('Add key',
+                () => Module.exports.keybinds_begin_rebind(action)));
+            row.insertCell().appendChild(button('Clear',
+                () => Module.exports.keybinds_clear(action)));
+        });
+
+        let rebinding = Module.exports.keybinds_rebinding();
+        let conflict = Module.exports.keybinds_conflict(0);
+        if (rebinding >= 0) {
+            status.textContent = 'Press a key for ' + ACTIONS[rebinding] + ' (Esc to cancel)';
+        } else if (conflict !== 0) {
+            status.textContent = 'Key ' + keyName(conflict) + ' is bound to several actions';
+        } else {
+            status.textContent = '';
+        }
+    };
+
+    document.getElementById('keybinds-reset')
+        .addEventListener('click', (event) => {
+            focusGame();
+            Module.exports.keybinds_reset();
+            update();
+        });
+    // the game sees key presses first, it may have finished rebinding
+    window.addEventListener('keydown', () => Promise.resolve().then(update));
+    update();
+
+    // learn key names before the keys are pressed, where the browser can tell them
+    if (navigator.keyboard && navigator.keyboard.getLayoutMap) {
+        navigator.keyboard.getLayoutMap().then((layout) => {
+            layout.forEach((key, code) => {
+                let payload = new PayloadWriter().string(code).string(key).put(Module);
+                Module.exports.key_name_learn(payload.ptr, payload.len);
+            });
+            update();
+        }).catch(() => {});
+    }
 };
 
+let controlSchemeUi = function(Module) {
+    let select = document.getElementById('control-scheme');
+    select.value = String(Module.exports.control_scheme());
+    select.addEventListener('change', () => {
+        Module.exports.set_control_scheme(Number(select.value));
+        // keep arrow keys from changing the selection
+        focusGame();
+    });
+};
+
+// in Style order, see render.rs
+const LAYER_NAMES = ['hud', 'ship', 'bullets', 'asteroids', 'explosions', 'overlay'];
+let layerElement = (layer) => document.getElementById('layer-' + LAYER_NAMES[layer]);
+// kept for drawing display lists in the same styles
+let layerStyles = LAYER_NAMES.map(() => ({ stroke: 'white', width: 2.5, opacity: 1 }));
+
 let svg = {
-    svg_set_path: (ptr, len) => window.path.setAttributeNS(null, 'd', getStr(Module, ptr, len)),
+    svg_set_path: (layer, ptr, len) => layerElement(layer).setAttributeNS(null, 'd', getStr(Module, ptr, len)),
+    svg_set_style: (layer, ptr, len, width, opacity) => {
+        let style = { stroke: getStr(Module, ptr, len), width: width, opacity: opacity };
+        layerStyles[layer] = style;
+        let element = layerElement(layer);
+        element.setAttributeNS(null, 'stroke', style.stroke);
+        element.setAttributeNS(null, 'stroke-width', style.width);
+        element.setAttributeNS(null, 'stroke-opacity', style.opacity);
+    },
+    // the canvas is transparent, the SVG background shows through it
+    svg_set_background: (ptr, len) => {
+        document.querySelector('svg').style.background = getStr(Module, ptr, len);
+    },
+    svg_set_transform: (ptr, len) =>
+        document.getElementById('scene').setAttributeNS(null, 'transform', getStr(Module, ptr, len)),
+    svg_set_clip: (x, y, width, height) => {
+        let rect = document.getElementById('scene-clip-rect');
+        rect.setAttributeNS(null, 'x', x);
+        rect.setAttributeNS(null, 'y', y);
+        rect.setAttributeNS(null, 'width', width);
+        rect.setAttributeNS(null, 'height', height);
+    },
+};
+
+/*
+ *     Draws the display list the game renders into its memory, see render.rs
+ * for the format. The canvas lies on top of the SVG, which still gets the
+ * input events.
+ */
+const DISPLAY_LIST_VIEWPORT = 1;
+const DISPLAY_LIST_LAYER = 2;
+const DISPLAY_LIST_POLYLINE = 3;
+
+let displayList = {
+    display_list_draw: (ptr, len) => {
+        let canvas = document.getElementById('canvas');
+        let dpr = window.devicePixelRatio || 1;
+        let width = Math.round(canvas.clientWidth * dpr);
+        let height = Math.round(canvas.clientHeight * dpr);
+        if (canvas.width !== width || canvas.height !== height) {
+            canvas.width = width;
+            canvas.height = height;
+        }
+        let ctx = canvas.getContext('2d');
+        ctx.setTransform(1, 0, 0, 1, 0, 0);
+        ctx.clearRect(0, 0, width, height);
+
+        let view = new DataView(Module.memory.buffer, ptr, len);
+        let pos = 0;
+        let f32 = () => { pos += 4; return view.getFloat32(pos - 4, true); };
+        ctx.save();
+        ctx.lineJoin = 'round';
+        while (pos < len) {
+            switch (view.getUint8(pos++)) {
+                case DISPLAY_LIST_VIEWPORT: {
+                    let x = f32(), y = f32(), scale = f32();
+                    ctx.setTransform(dpr * scale, 0, 0, dpr * scale, dpr * x, dpr * y);
+                    ctx.beginPath();
+                    ctx.rect(f32(), f32(), f32(), f32());
+                    ctx.clip();
+                    break;
+                }
+                case DISPLAY_LIST_LAYER: {
+                    let style = layerStyles[view.getUint8(pos++)];
+                    ctx.strokeStyle = style.stroke;
+                    // in scene units, scaled with it like the SVG stroke
+                    ctx.lineWidth = style.width;
+                    ctx.globalAlpha = style.opacity;
+                    break;
+                }
+                case DISPLAY_LIST_POLYLINE: {
+                    let count = view.getUint32(pos, true);
+                    pos += 4;
+                    ctx.beginPath();
+                    for (let i = 0; i < count; i++) {
+                        let x = f32(), y = f32();
+                        if (i === 0) { ctx.moveTo(x, y); } else { ctx.lineTo(x, y); }
+                    }
+                    ctx.stroke();
+                    break;
+                }
+                default:
+                    console.error('bad display list command at', pos - 1);
+                    pos = len;
+            }
+        }
+        ctx.restore();
+    },
+};
+
+let themeUi = function(Module) {
+    let select = document.getElementById('theme');
+    select.value = String(Module.exports.theme());
+    select.addEventListener('change', () => {
+        Module.exports.set_theme(Number(select.value));
+        focusGame();
+    });
+};
+
+let renderTargetUi = function(Module) {
+    let select = document.getElementById('render-target');
+    select.addEventListener('change', () => {
+        Module.exports.set_render_target(Number(select.value));
+        focusGame();
+    });
 };
 
 let math = {
@@ -183,7 +808,7 @@ let rand = {
 };
 
 let imports = {
-    env: Object.assign({}, time, eventLoop(Module), io, svg, math, rand),
+    env: Object.assign({}, time, eventLoop(Module), io, gamepad, storage, svg, displayList, math, rand),
 };
 
 fetch('/target/wasm32-unknown-unknown/release/svg_asteroids.wasm')
@@ -197,6 +822,13 @@ fetch('/target/wasm32-unknown-unknown/release/svg_asteroids.wasm')
         dealloc: exports.dealloc,
         memory: exports.memory,
         event_loop_cb: exports.event_loop_cb,
+        exports: exports,
     });
     exports.my_main();
+    focusGame();
+    gameUi(Module);
+    keybindsUi(Module);
+    controlSchemeUi(Module);
+    renderTargetUi(Module);
+    themeUi(Module);
 });
@@ -0,0 +1,17 @@
+// Runs a WASI test binary with node, for `cargo test --target wasm32-wasip1`:
+//
+//     CARGO_TARGET_WASM32_WASIP1_RUNNER="node sim/wasi_runner.js" cargo test --target wasm32-wasip1 --lib
+//
+// Tests read golden files by absolute paths, so the whole file system is visible.
+const { WASI } = require('node:wasi');
+const fs = require('fs');
+
+const wasi = new WASI({
+    version: 'preview1',
+    args: process.argv.slice(2),
+    env: process.env,
+    preopens: { '/': '/' },
+});
+const wasm = new WebAssembly.Module(fs.readFileSync(process.argv[2]));
+const instance = new WebAssembly.Instance(wasm, wasi.getImportObject());
+process.exitCode = wasi.start(instance);
@@ -0,0 +1,384 @@
+use std::f64::consts::PI;
+use crate::game::{Game, GameState, Config, AxisIndex};
+use crate::geom::{ray_circle};
+use crate::input::{InputIndex, AxisSource};
+use crate::math::{Vec2D, Transform2D};
+use crate::rng::{seeded_rng};
+
+/*
+ *     A Gym-style environment for bots and reinforcement learning, playing
+ * the real game: `reset` starts a seeded game, `step` applies an action
+ * for `frame_skip` ticks and returns what the agent sees, the reward and
+ * whether the episode is over. Like in the headless simulation, the next
+ * level and respawns start right away.
+ *
+ *     Observations are in the ship's frame of reference: x points where
+ * the ship is heading, y to its right. The field wraps around, so other
+ * objects are seen where they're closest to the ship.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Action {
+    // -1 turns left, 1 turns right
+    pub turn: f64,
+    // 1 is full thrust, negative values are ignored, like the Forward key
+    pub thrust: f64,
+    pub shoot: bool,
+}
+
+pub const NUMBER_OF_ACTIONS: usize = 12;
+
+impl Action {
+    pub fn idle() -> Action {
+        Action { turn: 0.0, thrust: 0.0, shoot: false }
+    }
+
+    /// One of `NUMBER_OF_ACTIONS` actions, every combination of
+    /// turning left, right or not at all, thrusting and shooting.
+    pub fn discrete(index: usize) -> Option<Action> {
+        if index >= NUMBER_OF_ACTIONS {
+            return None;
+        }
+        Some(Action {
+            turn: (index % 3) as f64 - 1.0,
+            thrust: ((index / 3) % 2) as f64,
+            shoot: index / 6 == 1,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RewardConfig {
+    // per point of game score, an asteroid is worth 100
+    pub score: f64,
+    // per tick the ship is alive
+    pub survival: f64,
+    // when the ship is destroyed, usually negative
+    pub death: f64,
+    // when a level is cleared
+    pub level: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct EnvConfig {
+    // ticks each action is repeated for
+    pub frame_skip: u32,
+    // the episode ends after this many ticks, if it's not over before
+    pub max_ticks: Option<u64>,
+    pub rewards: RewardConfig,
+    // how many of the nearest asteroids and bullets are observed
+    pub nearest_asteroids: usize,
+    pub nearest_bullets: usize,
+    // raycast sensor: number of rays, evenly spaced starting straight ahead
+    pub rays: usize,
+    pub ray_length: f64,
+}
+
+impl EnvConfig {
+    pub fn new() -> EnvConfig {
+        EnvConfig {
+            frame_skip: 4,
+            max_ticks: Some(60 * 60 * 5),
+            rewards: RewardConfig {
+                score: 0.01,
+                survival: 0.0,
+                death: -1.0,
+                level: 1.0,
+            },
+            nearest_asteroids: 8,
+            nearest_bullets: 4,
+            rays: 16,
+            ray_length: 600.0,
+        }
+    }
+}
+
+impl Default for EnvConfig {
+    fn default() -> EnvConfig {
+        EnvConfig::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShipObservation {
+    // field coordinates
+    pub pos: Vec2D,
+    // in the ship's frame
+    pub speed: Vec2D,
+    pub angle: f64,
+    pub angular_speed: f64,
+    pub dead: bool,
+}
+
+/// An object relative to the ship, in the ship's frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ObjectObservation {
+    pub pos: Vec2D,
+    pub speed: Vec2D,
+    pub size: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub ship: ShipObservation,
+    // nearest first, at most `EnvConfig::nearest_asteroids`
+    pub asteroids: Vec<ObjectObservation>,
+    pub bullets: Vec<ObjectObservation>,
+    // distance to the nearest asteroid along each ray, as a fraction
+    // of `EnvConfig::ray_length`, 1 if there's none in reach
+    pub rays: Vec<f64>,
+}
+
+impl Observation {
+    /// A fixed-size vector of numbers, for learning algorithms:
+    /// the ship, the objects padded with zeros, then the rays.
+    pub fn to_vec(&self, config: &EnvConfig) -> Vec<f64> {
+        let ship = &self.ship;
+        let mut values = vec![
+            ship.speed.x, ship.speed.y, ship.angle.cos(), ship.angle.sin(), ship.angular_speed,
+            if ship.dead { 1.0 } else { 0.0 },
+        ];
+        let mut push_objects = |objects: &[ObjectObservation], count: usize| {
+            for i in 0..count {
+                match objects.get(i) {
+                    Some(o) => values.extend_from_slice(&[1.0, o.pos.x, o.pos.y, o.speed.x, o.speed.y, o.size]),
+                    None => values.extend_from_slice(&[0.0; 6]),
+                }
+            }
+        };
+        push_objects(&self.asteroids, config.nearest_asteroids);
+        push_objects(&self.bullets, config.nearest_bullets);
+        values.extend_from_slice(&self.rays);
+        values
+    }
+}
+
+pub struct Environment {
+    pub config: EnvConfig,
+    game: Game,
+    ticks: u64,
+}
+
+impl Environment {
+    pub fn new(config: EnvConfig) -> Environment {
+        Environment {
+            config: config,
+            game: Game::with_rng(Config::new(), seeded_rng(0)),
+            ticks: 0,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Starts a new episode, the same seed plays the same way given the same actions.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Game::with_rng(Config::new(), seeded_rng(seed));
+        self.ticks = 0;
+        self.observe()
+    }
+
+    fn time(&self) -> f64 {
+        self.ticks as f64 * self.game.config.delta_t * 1e3
+    }
+
+    fn apply(&mut self, action: Action) {
+        let game = &mut self.game;
+        game.inputs.set_axis(AxisSource::Host, AxisIndex::Rotate, action.turn.clamp(-1.0, 1.0), &game.config);
+        game.inputs.set_axis(AxisSource::Host, AxisIndex::Thrust, action.thrust.clamp(0.0, 1.0), &game.config);
+        let shooting = game.inputs.is_down(InputIndex::Shoot);
+        if action.shoot && !shooting {
+            game.inputs.action_down(InputIndex::Shoot);
+        } else if !action.shoot && shooting {
+            game.inputs.action_up(InputIndex::Shoot);
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.game.game_state == GameState::GameOver ||
+            self.config.max_ticks.is_some_and(|max| self.ticks >= max)
+    }
+
+    /// Plays `action` for `frame_skip` ticks, or until the episode is over.
+    pub fn step(&mut self, action: Action) -> (Observation, f64, bool) {
+        let rewards = self.config.rewards;
+        let mut reward = 0.0;
+        for _ in 0..self.config.frame_skip.max(1) {
+            if self.is_done() {
+                break;
+            }
+            self.game.skip_menus();
+            // set after skipping menus, the contexts have changed
+            self.apply(action);
+            let (score, was_dead) = (self.game.score, self.game.ship.dead);
+            self.ticks += 1;
+            let time = self.time();
+            self.game.tick(time);
+
+            let game = &self.game;
+            reward += (game.score - score) as f64 * rewards.score;
+            if !game.ship.dead {
+                reward += rewards.survival;
+            } else if !was_dead {
+                reward += rewards.death;
+            }
+            if game.game_state == GameState::LevelCleared {
+                reward += rewards.level;
+            }
+        }
+        (self.observe(), reward, self.is_done())
+    }
+
+    pub fn observe(&self) -> Observation {
+        let game = &self.game;
+        let ship = &game.ship;
+        let field_size = game.config.field_size;
+        // into the ship's frame
+        let to_ship = Transform2D::rotate(-ship.angle);
+        let local = |v: Vec2D| to_ship.apply_vector(v);
+        let relative = |pos: Vec2D, speed: Vec2D, size: f64| ObjectObservation {
+            pos: local(pos.wrapped_sub(ship.pos, field_size)),
+            speed: local(speed - ship.speed),
+            size: size,
+        };
+        let nearest = |mut objects: Vec<ObjectObservation>, count: usize| {
+            objects.sort_by(|a, b| a.pos.len().total_cmp(&b.pos.len()));
+            objects.truncate(count);
+            objects
+        };
+
+        let asteroids: Vec<ObjectObservation> = game.asteroids.iter()
+            .map(|a| relative(a.pos, a.speed, a.size))
+            .collect();
+        let rays = (0..self.config.rays).map(|i| {
+            let dir = Vec2D::one().rotate(2.0 * PI * i as f64 / self.config.rays as f64);
+            let hit = asteroids.iter()
+                .filter_map(|a| ray_circle(Vec2D::zero(), dir, a.pos, a.size))
+                .fold(self.config.ray_length, f64::min);
+            hit / self.config.ray_length
+        }).collect();
+        let bullets = game.bullets.iter()
+            .map(|b| relative(b.pos, b.speed, 0.0))
+            .collect();
+
+        Observation {
+            ship: ShipObservation {
+                pos: ship.pos,
+                speed: local(ship.speed),
+                angle: ship.angle,
+                angular_speed: ship.angular_speed,
+                dead: ship.dead,
+            },
+            asteroids: nearest(asteroids, self.config.nearest_asteroids),
+            bullets: nearest(bullets, self.config.nearest_bullets),
+            rays: rays,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discrete_actions_cover_every_combination() {
+        let actions: Vec<Action> = (0..NUMBER_OF_ACTIONS).filter_map(Action::discrete).collect();
+        assert_eq!(actions.len(), NUMBER_OF_ACTIONS);
+        for (i, a) in actions.iter().enumerate() {
+            assert!(!actions[i + 1..].contains(a));
+        }
+        assert_eq!(Action::discrete(NUMBER_OF_ACTIONS), None);
+    }
+
+    #[test]
+    fn same_seed_same_episode() {
+        let play = || {
+            let mut env = Environment::new(EnvConfig::new());
+            env.reset(3);
+            let mut total = 0.0;
+            for i in 0..300 {
+                let (_, reward, done) = env.step(Action::discrete(i % NUMBER_OF_ACTIONS).unwrap());
+                total += reward;
+                if done { break; }
+            }
+            (env.observe(), total)
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn observations_are_relative_to_the_ship() {
+        let mut env = Environment::new(EnvConfig::new());
+        env.reset(1);
+        {
+            let game = &mut env.game;
+            game.asteroids.truncate(1);
+            let asteroid = &mut game.asteroids[0];
+            // straight ahead of the ship, which points up
+            asteroid.pos = game.ship.pos + Vec2D { x: 0.0, y: -200.0 };
+            asteroid.speed = Vec2D::zero();
+        }
+        let observation = env.observe();
+        let seen = observation.asteroids[0].pos;
+        assert!((seen.x - 200.0).abs() < 1e-6 && seen.y.abs() < 1e-6);
+        let expected = (200.0 - env.game.asteroids[0].size) / env.config.ray_length;
+        assert!((observation.rays[0] - expected).abs() < 1e-6);
+        assert_eq!(observation.rays[env.config.rays / 2], 1.0);
+        assert_eq!(observation.to_vec(&env.config).len(),
+                   6 + 6 * (env.config.nearest_asteroids + env.config.nearest_bullets) + env.config.rays);
+    }
+
+    #[test]
+    fn rewards_score() {
+        let mut config = EnvConfig::new();
+        config.frame_skip = 1;
+        config.rewards.level = 0.0;
+        let mut env = Environment::new(config);
+        env.reset(1);
+        {
+            let game = &mut env.game;
+            game.asteroids.truncate(1);
+            let asteroid = &mut game.asteroids[0];
+            asteroid.pos = game.ship.pos + Vec2D { x: 0.0, y: -200.0 };
+            asteroid.speed = Vec2D::zero();
+        }
+        let shoot = Action { turn: 0.0, thrust: 0.0, shoot: true };
+        let mut reward = env.step(shoot).1;
+        for _ in 0..60 {
+            if env.game.score > 0 {
+                break;
+            }
+            reward += env.step(Action::idle()).1;
+        }
+        assert!(env.game.score > 0, "the bullet should hit the asteroid");
+        assert!(!env.game.ship.dead);
+        assert!((reward - env.game.score as f64 * config.rewards.score).abs() < 1e-9);
+    }
+
+    #[test]
+    fn survival_reward_builds_up_per_tick() {
+        let mut config = EnvConfig::new();
+        config.frame_skip = 4;
+        config.rewards.survival = 0.5;
+        let mut env = Environment::new(config);
+        env.reset(1);
+        let (observation, reward, _) = env.step(Action::idle());
+        assert!(!observation.ship.dead);
+        assert_eq!(reward, 4.0 * 0.5);
+        assert_eq!(env.step(Action::idle()).1, 4.0 * 0.5);
+    }
+
+    #[test]
+    fn rewards_death() {
+        let mut config = EnvConfig::new();
+        config.frame_skip = 1;
+        let mut env = Environment::new(config);
+        env.reset(1);
+        let ship_pos = env.game.ship.pos;
+        env.game.asteroids[0].pos = ship_pos;
+        let (observation, reward, done) = env.step(Action::idle());
+        assert!(observation.ship.dead);
+        assert_eq!(reward, config.rewards.death);
+        assert!(!done);
+    }
+}
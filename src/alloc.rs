@@ -10,6 +10,7 @@ fn alloc(size: usize) -> *mut u8 {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C"
 fn dealloc(ptr: *mut u8, cap: usize) {
     unsafe  {
@@ -0,0 +1,324 @@
+/*
+ *     Functions exported to the page, for settings UI and such.
+ * Actions are passed as `InputIndex` numbers, keys as `KeyCode` numbers.
+ */
+use crate::{with_game, save_key_binds, save_control_scheme, update_subscriptions};
+use crate::render::{RenderTarget};
+use crate::theme::{THEMES};
+use crate::svg_export::{frame_svg};
+use std::cell::{RefCell};
+use crate::eventloop::{read_key};
+use crate::payload::{Payload};
+use crate::keycode::{KeyCode};
+use crate::input::{InputIndex, ControlScheme, AxisIndex, AxisSource, ResponseCurve};
+
+thread_local! {
+    static EXPORTED_SVG: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Starts collecting typed text, e.g. for a name prompt on the page.
+/// Keys are bound to text entry actions until it's submitted or cancelled.
+#[no_mangle]
+pub extern "C"
+fn text_entry_begin() {
+    with_game(|game| game.begin_text_entry());
+}
+
+#[no_mangle]
+pub extern "C"
+fn text_entry_active() -> bool {
+    with_game(|game| game.text_entry.is_some()).unwrap_or(false)
+}
+
+/// Pointer to the text typed so far, or submitted text once text entry is over.
+/// Valid until the next event is handled.
+#[no_mangle]
+pub extern "C"
+fn text_entry_ptr() -> *const u8 {
+    with_game(|game| {
+        game.text_entry.as_ref().or(game.entered_text.as_ref())
+            .map_or(::std::ptr::null(), |text| text.as_ptr())
+    }).unwrap_or(::std::ptr::null())
+}
+
+#[no_mangle]
+pub extern "C"
+fn text_entry_len() -> usize {
+    with_game(|game| {
+        game.text_entry.as_ref().or(game.entered_text.as_ref())
+            .map_or(0, |text| text.len())
+    }).unwrap_or(0)
+}
+
+/// Sets an axis value in range -1..1 for analog devices the page handles itself.
+#[no_mangle]
+pub extern "C"
+fn set_axis(axis: u32, value: f64) -> bool {
+    let axis = match AxisIndex::from_u32(axis) {
+        Some(axis) => axis,
+        None => return false,
+    };
+    with_game(|game| game.inputs.set_axis(AxisSource::Host, axis, value, &game.config))
+        .is_some()
+}
+
+#[no_mangle]
+pub extern "C"
+fn set_axis_response(axis: u32, dead_zone: f64, curve: u32) -> bool {
+    let (axis, curve) = match (AxisIndex::from_u32(axis), ResponseCurve::from_u32(curve)) {
+        (Some(axis), Some(curve)) => (axis, curve),
+        _ => return false,
+    };
+    with_game(|game| {
+        let axis = &mut game.config.axes[axis as usize];
+        axis.dead_zone = dead_zone.clamp(0.0, 0.95);
+        axis.curve = curve;
+    }).is_some()
+}
+
+/// Starts a new game like `my_main`, but with `MathMode::Deterministic` and
+/// the rng seeded with `seed`, so that the same input plays the same way
+/// and gives the same checksums on every target.
+#[no_mangle]
+pub extern "C"
+fn start_deterministic_game(seed: u32) {
+    crate::start_game(Some(u64::from(seed)));
+}
+
+/// `Game::checksum` of the current state, 0 without a game.
+/// It's a `u64`, which the page gets as a `BigInt`.
+#[no_mangle]
+pub extern "C"
+fn game_checksum() -> u64 {
+    with_game(|game| game.checksum()).unwrap_or(0)
+}
+
+/// Switches between drawing the SVG path and the display list, see `RenderTarget`.
+#[no_mangle]
+pub extern "C"
+fn set_render_target(target: u32) -> bool {
+    match RenderTarget::from_u32(target) {
+        Some(target) => {
+            crate::set_render_target(target);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Renders the current frame as a standalone SVG document, see `svg_export`,
+/// and returns its length in bytes, 0 without a game.
+#[no_mangle]
+pub extern "C"
+fn svg_export_frame() -> usize {
+    let theme = crate::current_theme();
+    let svg = with_game(|game| frame_svg(game, theme)).unwrap_or_default();
+    EXPORTED_SVG.with(|exported| {
+        *exported.borrow_mut() = svg;
+        exported.borrow().len()
+    })
+}
+
+/// The document of the last `svg_export_frame`, valid until the next one.
+#[no_mangle]
+pub extern "C"
+fn svg_export_ptr() -> *const u8 {
+    EXPORTED_SVG.with(|exported| exported.borrow().as_ptr())
+}
+
+/// The theme's number, in `THEMES` order.
+#[no_mangle]
+pub extern "C"
+fn theme() -> u32 {
+    let current = crate::current_theme();
+    THEMES.iter().position(|theme| theme == current).unwrap_or(0) as u32
+}
+
+/// Restyles the layers right away, and remembers the theme for the next game.
+#[no_mangle]
+pub extern "C"
+fn set_theme(theme: u32) -> bool {
+    match THEMES.get(theme as usize) {
+        Some(theme) => {
+            crate::set_theme(theme);
+            true
+        },
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C"
+fn control_scheme() -> u32 {
+    with_game(|game| game.config.control_scheme as u32).unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern "C"
+fn set_control_scheme(scheme: u32) -> bool {
+    let scheme = match ControlScheme::from_u32(scheme) {
+        Some(scheme) => scheme,
+        None => return false,
+    };
+    let changed = with_game(|game| {
+        game.set_control_scheme(scheme);
+        save_control_scheme(scheme);
+    }).is_some();
+    if changed {
+        update_subscriptions(scheme);
+    }
+    changed
+}
+
+/// Returns the action the game is waiting a key for, or -1.
+#[no_mangle]
+pub extern "C"
+fn keybinds_rebinding() -> i32 {
+    with_game(|game| game.rebinding.map_or(-1, |input| input as i32))
+        .unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "C"
+fn keybinds_begin_rebind(action: u32) -> bool {
+    let input = match InputIndex::from_u32(action) {
+        Some(input) => input,
+        None => return false,
+    };
+    with_game(|game| game.begin_rebind(input)).is_some()
+}
+
+#[no_mangle]
+pub extern "C"
+fn keybinds_cancel_rebind() {
+    with_game(|game| game.rebinding = None);
+}
+
+/// Returns the `InputContext` the action belongs to.
+#[no_mangle]
+pub extern "C"
+fn action_context(action: u32) -> u32 {
+    InputIndex::from_u32(action).map_or(0, |input| input.context() as u32)
+}
+
+/// Returns the active `InputContext`.
+#[no_mangle]
+pub extern "C"
+fn input_context() -> u32 {
+    with_game(|game| game.inputs.context() as u32).unwrap_or(0)
+}
+
+pub const ACTION_DOWN: u32 = 1;
+pub const ACTION_JUST_PRESSED: u32 = 2;
+pub const ACTION_JUST_RELEASED: u32 = 4;
+
+/// Returns `ACTION_*` flags for the action since the last tick.
+#[no_mangle]
+pub extern "C"
+fn action_state(action: u32) -> u32 {
+    let input = match InputIndex::from_u32(action) {
+        Some(input) => input,
+        None => return 0,
+    };
+    with_game(|game| {
+        let inputs = &game.inputs;
+        (if inputs.is_down(input) { ACTION_DOWN } else { 0 })
+            | (if inputs.just_pressed(input) { ACTION_JUST_PRESSED } else { 0 })
+            | (if inputs.just_released(input) { ACTION_JUST_RELEASED } else { 0 })
+    }).unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern "C"
+fn keybinds_count(action: u32) -> u32 {
+    InputIndex::from_u32(action)
+        .and_then(|input| with_game(|game| game.config.key_binds.keys_for(input).len() as u32))
+        .unwrap_or(0)
+}
+
+/// Returns the `idx`-th key bound to `action`, or 0 (`KeyCode::Unidentified`).
+#[no_mangle]
+pub extern "C"
+fn keybinds_get(action: u32, idx: u32) -> u32 {
+    InputIndex::from_u32(action)
+        .and_then(|input| with_game(|game| game.config.key_binds.keys_for(input)))
+        .and_then(|keys| keys.get(idx as usize).cloned())
+        .unwrap_or(KeyCode::Unidentified) as u32
+}
+
+#[no_mangle]
+pub extern "C"
+fn keybinds_unbind(action: u32, code: u32) -> bool {
+    let (input, code) = match (InputIndex::from_u32(action), KeyCode::from_u32(code)) {
+        (Some(input), Some(code)) => (input, code),
+        _ => return false,
+    };
+    with_game(|game| {
+        let changed = game.config.key_binds.unbind(code, input);
+        if changed {
+            save_key_binds(&game.config.key_binds);
+        }
+        changed
+    }).unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C"
+fn keybinds_clear(action: u32) {
+    if let Some(input) = InputIndex::from_u32(action) {
+        with_game(|game| {
+            game.config.key_binds.clear(input);
+            save_key_binds(&game.config.key_binds);
+        });
+    }
+}
+
+#[no_mangle]
+pub extern "C"
+fn keybinds_reset() {
+    with_game(|game| {
+        game.config.key_binds.reset();
+        save_key_binds(&game.config.key_binds);
+    });
+}
+
+/// Returns the key bound to more than one action, or 0 if there's no conflict.
+/// With several conflicts, `idx` selects one of them.
+#[no_mangle]
+pub extern "C"
+fn keybinds_conflict(idx: u32) -> u32 {
+    with_game(|game| game.config.key_binds.conflicts())
+        .and_then(|conflicts| conflicts.get(idx as usize).map(|conflict| conflict.0))
+        .unwrap_or(KeyCode::Unidentified) as u32
+}
+
+/// Pointer to the name of the key in the player's keyboard layout.
+/// Valid until the next event is handled.
+#[no_mangle]
+pub extern "C"
+fn key_name_ptr(code: u32) -> *const u8 {
+    KeyCode::from_u32(code)
+        .and_then(|code| with_game(|game| game.config.key_names.name(code).as_ptr()))
+        .unwrap_or(::std::ptr::null())
+}
+
+#[no_mangle]
+pub extern "C"
+fn key_name_len(code: u32) -> usize {
+    KeyCode::from_u32(code)
+        .and_then(|code| with_game(|game| game.config.key_names.name(code).len()))
+        .unwrap_or(0)
+}
+
+/// Tells the name of a key before it's pressed, e.g. from the Keyboard Map API.
+/// Takes ownership of an `alloc`ed payload with the `code` and `key` strings,
+/// like the one of key events.
+#[no_mangle]
+pub extern "C"
+fn key_name_learn(ptr: u32, len: u32) {
+    // the page passes a buffer from `alloc`, like event payloads
+    let mut payload = unsafe { Payload::from_host(ptr, len) };
+    if let Some((code, key)) = read_key(&mut payload) {
+        with_game(|game| game.config.key_names.learn(code, &key));
+    }
+}
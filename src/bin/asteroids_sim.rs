@@ -0,0 +1,123 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use svg_asteroids::numeric::{MathMode};
+use svg_asteroids::sim::{InputScript, Simulation};
+use svg_asteroids::svg_export::{frame_svg, SvgAnimation};
+use svg_asteroids::theme::{self, Theme, THEMES};
+
+const USAGE: &str = "usage: asteroids-sim [--seed N] [--ticks N] [--script FILE] [--trace] [--deterministic]
+                     [--svg FILE] [--svg-animation FILE] [--svg-every N] [--theme NAME]
+
+Plays a seeded game with scripted input, without rendering, and prints a summary.
+The script format is described in src/sim.rs. With --deterministic, the math is
+done in software, and the checksum is the same on every platform.
+
+--svg writes the last frame as an SVG document, --svg-animation the whole run
+as an animated SVG, with a frame every N ticks (4 by default). Themes are
+classic (the default), neon, high-contrast and amber.";
+
+struct Options {
+    seed: u64,
+    ticks: u64,
+    script: Option<String>,
+    trace: bool,
+    math: MathMode,
+    svg: Option<String>,
+    svg_animation: Option<String>,
+    svg_every: u64,
+    theme: &'static Theme,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        seed: 0,
+        ticks: 60 * 60,
+        script: None,
+        trace: false,
+        math: MathMode::Native,
+        svg: None,
+        svg_animation: None,
+        svg_every: 4,
+        theme: &theme::CLASSIC,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--seed" => options.seed = value("--seed")?.parse().map_err(|_| "bad --seed".to_string())?,
+            "--ticks" => options.ticks = value("--ticks")?.parse().map_err(|_| "bad --ticks".to_string())?,
+            "--script" => options.script = Some(value("--script")?.clone()),
+            "--trace" => options.trace = true,
+            "--deterministic" => options.math = MathMode::Deterministic,
+            "--svg" => options.svg = Some(value("--svg")?.clone()),
+            "--svg-animation" => options.svg_animation = Some(value("--svg-animation")?.clone()),
+            "--svg-every" => options.svg_every = match value("--svg-every")?.parse() {
+                Ok(every) if every > 0 => every,
+                _ => return Err("bad --svg-every".to_string()),
+            },
+            "--theme" => {
+                let name = value("--theme")?;
+                options.theme = Theme::by_name(name).ok_or_else(|| {
+                    let names: Vec<&str> = THEMES.iter().map(|theme| theme.name).collect();
+                    format!("unknown theme `{}`, expected one of {}", name, names.join(", "))
+                })?;
+            },
+            other => return Err(format!("unknown argument `{}`", other)),
+        }
+    }
+    Ok(options)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let script = match options.script.as_ref() {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            InputScript::parse(&text).map_err(|err| format!("{}: {}", path, err))?
+        },
+        None => InputScript::default(),
+    };
+
+    let mut sim = Simulation::with_math(options.seed, script, options.math);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let frame_ms = sim.game.config.delta_t * 1e3 * (options.svg_every as f64);
+    let mut animation = options.svg_animation.as_ref()
+        .map(|_| SvgAnimation::new(&sim.game.viewport, options.theme, frame_ms));
+    let (trace, every) = (options.trace, options.svg_every);
+    let summary = sim.run_with(options.ticks, |sim| {
+        if trace {
+            writeln!(out, "{}", sim.trace_line())?;
+        }
+        if let Some(animation) = animation.as_mut() {
+            if sim.game.tick % every == 0 {
+                animation.add_frame(&sim.game);
+            }
+        }
+        Ok(())
+    }).map_err(|err| err.to_string())?;
+
+    let write = |path: &str, svg: String| fs::write(path, svg).map_err(|err| format!("{}: {}", path, err));
+    if let Some(path) = options.svg.as_ref() {
+        write(path, frame_svg(&sim.game, options.theme))?;
+    }
+    if let (Some(path), Some(animation)) = (options.svg_animation.as_ref(), animation) {
+        write(path, animation.to_svg())?;
+    }
+    writeln!(out, "{}", summary).map_err(|err| err.to_string())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let result = parse_args(&args).and_then(run);
+    if let Err(err) = result {
+        eprintln!("asteroids-sim: {}\n\n{}", err, USAGE);
+        process::exit(2);
+    }
+}
@@ -1,78 +1,738 @@
 use std::cell::{RefCell};
-use std::collections::{HashMap};
-
-extern "C" {
-    fn event_loop_new() -> u32;
-    fn event_loop_raf(id: u32);
-    fn event_loop_shutdown(id: u32) -> bool;
-}
+use std::collections::{HashMap, VecDeque};
+use std::mem::{ManuallyDrop};
+use std::time::{Duration};
+use crate::keycode::{KeyCode, Modifiers};
+use crate::payload::{Payload};
+use crate::host::{host};
 
 const EVENT_DESTROYED: u32 = 0;
 const EVENT_ANIMATION_FRAME: u32 = 1;
 const EVENT_MOUSE_MOVE: u32 = 2;
 const EVENT_KEY_DOWN: u32 = 3;
 const EVENT_KEY_UP: u32 = 4;
+const EVENT_GAMEPAD: u32 = 5;
+const EVENT_MOUSE_DOWN: u32 = 6;
+const EVENT_MOUSE_UP: u32 = 7;
+const EVENT_WHEEL: u32 = 8;
+const EVENT_TOUCH_START: u32 = 9;
+const EVENT_TOUCH_MOVE: u32 = 10;
+const EVENT_TOUCH_END: u32 = 11;
+const EVENT_TIMER: u32 = 12;
+const EVENT_VISIBILITY_CHANGE: u32 = 13;
+const EVENT_FOCUS: u32 = 14;
+const EVENT_BLUR: u32 = 15;
+const EVENT_RESIZE: u32 = 16;
+const EVENT_POINTER_DOWN: u32 = 17;
+const EVENT_POINTER_MOVE: u32 = 18;
+const EVENT_POINTER_UP: u32 = 19;
+const EVENT_TEXT_INPUT: u32 = 20;
+const EVENT_MESSAGE: u32 = 21;
+
+/*
+ *     Event categories a loop may subscribe to, see `EventLoop::subscribe`.
+ * A new loop gets nothing but animation frames, timers and gamepads,
+ * which it asks for explicitly.
+ */
+// `MouseMove`
+pub const EVENTS_MOUSE_MOVE: u32 = 1;
+// `MouseDown`, `MouseUp`
+pub const EVENTS_MOUSE_BUTTONS: u32 = 2;
+// `Wheel`
+pub const EVENTS_WHEEL: u32 = 4;
+// `KeyDown`, `KeyUp`
+pub const EVENTS_KEYBOARD: u32 = 8;
+// `TouchStart`, `TouchMove`, `TouchEnd`
+pub const EVENTS_TOUCH: u32 = 16;
+// `PointerDown`, `PointerMove`, `PointerUp`
+pub const EVENTS_POINTER: u32 = 32;
+// `VisibilityChange`, `Focus`, `Blur`, `Resize`
+pub const EVENTS_PAGE: u32 = 64;
+// `TextInput`
+pub const EVENTS_TEXT: u32 = 128;
+// `Message`
+pub const EVENTS_MESSAGE: u32 = 256;
+
+// Key event flags
+const KEY_FLAG_SHIFT: u32 = 1;
+const KEY_FLAG_CTRL: u32 = 2;
+const KEY_FLAG_ALT: u32 = 4;
+// the key is held and the event is an auto-repeat
+const KEY_FLAG_REPEAT: u32 = 8;
+const KEY_FLAG_META: u32 = 16;
 
-#[derive(Copy,Clone)]
+#[derive(Clone)]
 pub enum Event {
     Destroyed,
-    AnimationFrame,
-    MouseMove { x: u32, y: u32 },
-    KeyDown { code: u32, chr: Option<char>, flags: u32 },
-    KeyUp { code: u32, chr: Option<char>, flags: u32 },
+    // `time` is the host timestamp in milliseconds, the same clock as `Instant`
+    AnimationFrame { time: f64 },
+    // mouse coordinates are relative to the SVG element
+    MouseMove { x: i32, y: i32 },
+    MouseDown { x: i32, y: i32, button: u32, time: f64 },
+    MouseUp { x: i32, y: i32, button: u32, time: f64 },
+    Wheel { dx: i32, dy: i32 },
+    // the touches that have changed, payload: list of (u32 id, i32 x, i32 y)
+    TouchStart { touches: Vec<Touch>, time: f64 },
+    TouchMove { touches: Vec<Touch>, time: f64 },
+    TouchEnd { touches: Vec<Touch>, time: f64 },
+    // `key` is the `KeyboardEvent.key` value, e.g. "a", "A" or "Shift",
+    // payload: string code, string key
+    KeyDown { code: KeyCode, key: String, modifiers: Modifiers, repeat: bool, time: f64 },
+    KeyUp { code: KeyCode, key: String, modifiers: Modifiers, time: f64 },
+    // `buttons` is a bitmask of pressed buttons, `button_values` are their analog values,
+    // payload: u32 index, u32 buttons, list of f64 axes, list of f64 button values
+    Gamepad { index: u32, buttons: u32, axes: Vec<f64>, button_values: Vec<f64> },
+    Timer { handle: TimerHandle, time: f64 },
+    // the page was hidden or shown, e.g. by switching tabs
+    VisibilityChange { visible: bool },
+    // the page gained or lost keyboard focus
+    Focus,
+    Blur,
+    // the SVG element was resized, `width` and `height` are in CSS pixels,
+    // `dpr` is the number of device pixels per CSS pixel
+    Resize { width: u32, height: u32, dpr: f64 },
+    // pointer events cover mice, pens and touches alike, `id` tells pointers apart
+    PointerDown { id: u32, kind: PointerKind, x: i32, y: i32, time: f64 },
+    PointerMove { id: u32, kind: PointerKind, x: i32, y: i32, time: f64 },
+    PointerUp { id: u32, kind: PointerKind, x: i32, y: i32, time: f64 },
+    // text typed or pasted into the page, payload: string text
+    TextInput { text: String, time: f64 },
+    // a message from another part of the page or from the network,
+    // payload: string channel, bytes data
+    Message { channel: String, data: Vec<u8>, time: f64 },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Touch {
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PointerKind {
+    Mouse = 0,
+    Pen = 1,
+    Touch = 2,
+}
+
+// The pointer kind is sent in the top byte of the pointer id
+fn unpack_pointer(packed: u32) -> (u32, PointerKind) {
+    let kind = match packed >> 24 {
+        1 => PointerKind::Pen,
+        2 => PointerKind::Touch,
+        _ => PointerKind::Mouse,
+    };
+    (packed & 0xff_ffff, kind)
+}
+
+/// Identifies a timer started with `set_timeout` or `set_interval`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TimerHandle(u32);
+
+impl TimerHandle {
+    pub(crate) fn from_u32(handle: u32) -> TimerHandle {
+        TimerHandle(handle)
+    }
+}
+
+fn unpack_modifiers(flags: u32) -> Modifiers {
+    Modifiers {
+        shift: flags & KEY_FLAG_SHIFT != 0,
+        ctrl: flags & KEY_FLAG_CTRL != 0,
+        alt: flags & KEY_FLAG_ALT != 0,
+        meta: flags & KEY_FLAG_META != 0,
+    }
+}
+
+// Key events carry the `code` and `key` strings
+pub(crate) fn read_key(payload: &mut Payload) -> Option<(KeyCode, String)> {
+    let code = KeyCode::parse(&payload.string()?);
+    let key = payload.string()?;
+    Some((code, key))
+}
+
+fn read_touch(payload: &mut Payload) -> Option<Touch> {
+    Some(Touch {
+        id: payload.u32()?,
+        x: payload.i32()?,
+        y: payload.i32()?,
+    })
+}
+
+fn read_gamepad(payload: &mut Payload) -> Option<Event> {
+    Some(Event::Gamepad {
+        index: payload.u32()?,
+        buttons: payload.u32()?,
+        axes: payload.list(Payload::f64)?,
+        button_values: payload.list(Payload::f64)?,
+    })
+}
+
+/*
+ *     Events with a payload get its pointer and length in `p0` and `p1`,
+ * see payload.rs for the format and who frees it. Malformed payloads
+ * are freed and the event is dropped.
+ */
+fn read_payload_event(msg: u32, mut payload: Payload, p2: u32, time: f64) -> Option<Event> {
+    let payload = &mut payload;
+    Some(match msg {
+        EVENT_KEY_DOWN => {
+            let (code, key) = read_key(payload)?;
+            let repeat = p2 & KEY_FLAG_REPEAT != 0;
+            Event::KeyDown { code: code, key: key, modifiers: unpack_modifiers(p2), repeat: repeat, time: time }
+        },
+        EVENT_KEY_UP => {
+            let (code, key) = read_key(payload)?;
+            Event::KeyUp { code: code, key: key, modifiers: unpack_modifiers(p2), time: time }
+        },
+        EVENT_TOUCH_START => Event::TouchStart { touches: payload.list(read_touch)?, time: time },
+        EVENT_TOUCH_MOVE => Event::TouchMove { touches: payload.list(read_touch)?, time: time },
+        EVENT_TOUCH_END => Event::TouchEnd { touches: payload.list(read_touch)?, time: time },
+        EVENT_GAMEPAD => read_gamepad(payload)?,
+        EVENT_TEXT_INPUT => Event::TextInput { text: payload.string()?, time: time },
+        EVENT_MESSAGE => Event::Message { channel: payload.string()?, data: payload.bytes()?, time: time },
+        _ => return None,
+    })
+}
+
+fn has_payload(msg: u32) -> bool {
+    matches!(msg,
+        EVENT_KEY_DOWN | EVENT_KEY_UP |
+        EVENT_TOUCH_START | EVENT_TOUCH_MOVE | EVENT_TOUCH_END |
+        EVENT_GAMEPAD | EVENT_TEXT_INPUT | EVENT_MESSAGE)
 }
 
 #[no_mangle]
 pub extern "C"
-fn event_loop_cb(id: u32, msg: u32, p0: u32, p1: u32, p2: u32) {
-    EVENTLOOPS.with(|el| {
-        let mut el = el.borrow_mut();
-        let mut fake_event_loop = EventLoop { id: id };
-
-        let event = match msg {
-            EVENT_DESTROYED => {
-                if let Some(mut cb) = el.remove(&id) {
-                    cb(Event::Destroyed, &mut fake_event_loop);
-                }
-                return;
-            },
-            EVENT_ANIMATION_FRAME => Event::AnimationFrame,
-            EVENT_MOUSE_MOVE => Event::MouseMove { x: p0, y: p1 },
-            EVENT_KEY_DOWN => Event::KeyDown { code: p0, chr: ::std::char::from_u32(p1), flags: p2 },
-            EVENT_KEY_UP => Event::KeyUp { code: p0, chr: ::std::char::from_u32(p1), flags: p2 },
-            _ => return,
+fn event_loop_cb(id: u32, msg: u32, p0: u32, p1: u32, p2: u32, time: f64) {
+    if has_payload(msg) {
+        // the host passes a buffer from `alloc`, see payload.rs
+        let payload = unsafe { Payload::from_host(p0, p1) };
+        if let Some(event) = read_payload_event(msg, payload, p2, time) {
+            deliver(id, event);
+        }
+        return;
+    }
+    let event = match msg {
+        EVENT_DESTROYED => Event::Destroyed,
+        EVENT_ANIMATION_FRAME => Event::AnimationFrame { time: time },
+        EVENT_MOUSE_MOVE => Event::MouseMove { x: p0 as i32, y: p1 as i32 },
+        EVENT_MOUSE_DOWN => Event::MouseDown { x: p0 as i32, y: p1 as i32, button: p2, time: time },
+        EVENT_MOUSE_UP => Event::MouseUp { x: p0 as i32, y: p1 as i32, button: p2, time: time },
+        EVENT_WHEEL => Event::Wheel { dx: p0 as i32, dy: p1 as i32 },
+        EVENT_TIMER => Event::Timer { handle: TimerHandle::from_u32(p0), time: time },
+        EVENT_VISIBILITY_CHANGE => Event::VisibilityChange { visible: p0 != 0 },
+        EVENT_FOCUS => Event::Focus,
+        EVENT_BLUR => Event::Blur,
+        // the device pixel ratio is sent in thousandths
+        EVENT_RESIZE => Event::Resize { width: p0, height: p1, dpr: f64::from(p2) / 1000.0 },
+        EVENT_POINTER_DOWN => {
+            let (id, kind) = unpack_pointer(p0);
+            Event::PointerDown { id: id, kind: kind, x: p1 as i32, y: p2 as i32, time: time }
+        },
+        EVENT_POINTER_MOVE => {
+            let (id, kind) = unpack_pointer(p0);
+            Event::PointerMove { id: id, kind: kind, x: p1 as i32, y: p2 as i32, time: time }
+        },
+        EVENT_POINTER_UP => {
+            let (id, kind) = unpack_pointer(p0);
+            Event::PointerUp { id: id, kind: kind, x: p1 as i32, y: p2 as i32, time: time }
+        },
+        _ => return,
+    };
+    deliver(id, event);
+}
+
+/// Delivers an event the host has decoded itself, see `NativeHost`.
+pub(crate) fn deliver(id: u32, event: Event) {
+    let start = with_dispatcher(|d| {
+        d.queue.push_back((id, event));
+        !::std::mem::replace(&mut d.dispatching, true)
+    });
+    // an event sent from inside a handler waits for the current one
+    if !start {
+        return;
+    }
+    while let Some((id, event)) = with_dispatcher(|d| d.queue.pop_front()) {
+        dispatch(id, event);
+    }
+    with_dispatcher(|d| d.dispatching = false);
+}
+
+/*
+ *     Handlers are called without the dispatcher borrowed, so they may
+ * create and shut down event loops, add and remove handlers, and the host
+ * may send events from inside them. The ordering guarantees are:
+ *
+ * - events are delivered in the order the host sends them, an event sent
+ *   while another one is being handled is queued, and delivered once
+ *   the current one has been delivered to all handlers;
+ * - handlers of a loop are called in the order they were added;
+ * - handlers added while an event is being delivered don't get that event,
+ *   they're registered once it has been delivered to all handlers;
+ * - removed handlers are never called again, not even for the event
+ *   being delivered, and are dropped once they return;
+ * - `Destroyed` is the last event handlers of a loop get,
+ *   they're dropped after it has been delivered.
+ */
+fn dispatch(id: u32, event: Event) {
+    // the handlers may use the loop, but don't own it
+    let mut fake_event_loop = ManuallyDrop::new(EventLoop { id: id });
+    let handlers: Vec<HandlerId> = with_dispatcher(|d| {
+        d.loops.get(&id)
+            .map_or(Vec::new(), |handlers| handlers.iter().map(|handler| handler.id).collect())
+    });
+    for handler_id in handlers {
+        let cb = with_dispatcher(|d| {
+            d.handler_mut(id, handler_id).and_then(|handler| handler.cb.take())
+        });
+        let mut cb = match cb {
+            Some(cb) => cb,
+            None => continue,
         };
+        cb(event.clone(), &mut fake_event_loop);
+        let removed = with_dispatcher(|d| match d.handler_mut(id, handler_id) {
+            Some(handler) => {
+                handler.cb = Some(cb);
+                None
+            },
+            None => Some(cb),
+        });
+        drop(removed);
+    }
 
-        if let Some(cb) = el.get_mut(&id) {
-            cb(event, &mut fake_event_loop);
+    let (destroyed, added) = with_dispatcher(|d| {
+        let destroyed = match event {
+            Event::Destroyed => d.loops.remove(&id),
+            _ => None,
+        };
+        let mut rejected = Vec::new();
+        for (loop_id, handler) in d.added.drain(..) {
+            match d.loops.get_mut(&loop_id) {
+                Some(handlers) => handlers.push(handler),
+                // the loop was destroyed before the handler was registered
+                None => rejected.push(handler),
+            }
         }
+        (destroyed, rejected)
     });
+    drop(destroyed);
+    drop(added);
+}
+
+/// Identifies a handler added with `EventLoop::add_handler`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct HandlerId(u32);
+
+struct Handler {
+    id: HandlerId,
+    // taken out while the handler is running
+    cb: Option<EventLoopCb>,
+}
+
+struct Dispatcher {
+    loops: HashMap<u32, Vec<Handler>>,
+    // events sent while another one is being delivered
+    queue: VecDeque<(u32, Event)>,
+    dispatching: bool,
+    // handlers added while an event is being delivered
+    added: Vec<(u32, Handler)>,
+    next_handler: u32,
+}
+
+impl Dispatcher {
+    fn handler_mut(&mut self, id: u32, handler_id: HandlerId) -> Option<&mut Handler> {
+        self.loops.get_mut(&id)
+            .and_then(|handlers| handlers.iter_mut().find(|handler| handler.id == handler_id))
+    }
 }
 
 thread_local! {
-    static EVENTLOOPS: RefCell<HashMap<u32, EventLoopCb>> = RefCell::new(HashMap::new());
+    static DISPATCHER: RefCell<Dispatcher> = RefCell::new(Dispatcher {
+        loops: HashMap::new(),
+        queue: VecDeque::new(),
+        dispatching: false,
+        added: Vec::new(),
+        next_handler: 0,
+    });
 }
 
-pub type EventLoopCb = Box<FnMut(Event, &mut EventLoop)>;
+fn with_dispatcher<F, R>(f: F) -> R
+    where F: FnOnce(&mut Dispatcher) -> R
+{
+    DISPATCHER.with(|d| f(&mut d.borrow_mut()))
+}
+
+pub type EventLoopCb = Box<dyn FnMut(Event, &mut EventLoop)>;
 
+/*
+ *     Lifecycle of an event loop: `shutdown` (or dropping the `EventLoop`)
+ * unsubscribes it from host events and cancels its timers, then the host
+ * delivers a single `Destroyed` event, after which the handlers are dropped.
+ * The host delivers it asynchronously, since the loop may be shut down
+ * from its own handler.
+ */
 pub struct EventLoop {
     id: u32,
 }
 
 impl EventLoop {
     pub fn new(cb: EventLoopCb) -> EventLoop {
-        let id = unsafe { event_loop_new() };
-        EVENTLOOPS.with(|el| {
-            el.borrow_mut().insert(id, cb);
+        let id = host().event_loop_new();
+        with_dispatcher(|d| d.loops.insert(id, Vec::new()));
+        let mut event_loop = EventLoop { id: id };
+        event_loop.add_handler(cb);
+        event_loop
+    }
+
+    /// Adds another handler, called after the ones added before it.
+    pub fn add_handler(&mut self, cb: EventLoopCb) -> HandlerId {
+        let id = self.id;
+        with_dispatcher(|d| {
+            d.next_handler += 1;
+            let handler = Handler { id: HandlerId(d.next_handler), cb: Some(cb) };
+            let handler_id = handler.id;
+            if d.dispatching {
+                d.added.push((id, handler));
+            } else if let Some(handlers) = d.loops.get_mut(&id) {
+                handlers.push(handler);
+            }
+            handler_id
+        })
+    }
+
+    /// Returns false if there's no such handler.
+    pub fn remove_handler(&mut self, handler_id: HandlerId) -> bool {
+        let id = self.id;
+        let removed = with_dispatcher(|d| {
+            if let Some(pos) = d.added.iter().position(|entry| entry.0 == id && entry.1.id == handler_id) {
+                return Some(d.added.remove(pos));
+            }
+            let handlers = d.loops.get_mut(&id)?;
+            let pos = handlers.iter().position(|handler| handler.id == handler_id)?;
+            // a running handler is dropped by `dispatch` once it returns
+            Some((id, handlers.remove(pos)))
         });
-        EventLoop { id: id }
+        removed.is_some()
     }
 
+    /// Requests made before the next frame are merged into one.
     pub fn request_animation_frame(&mut self) {
-        unsafe { event_loop_raf(self.id); }
+        host().event_loop_raf(self.id);
+    }
+
+    /*
+     *     Timers are run by the host, independently of animation frames,
+     * and deliver `Event::Timer` when they fire. They're cancelled
+     * when the loop shuts down.
+     */
+    pub fn set_timeout(&mut self, delay: Duration) -> TimerHandle {
+        TimerHandle(host().event_loop_set_timer(self.id, delay.as_secs_f64() * 1e3, false))
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) -> TimerHandle {
+        TimerHandle(host().event_loop_set_timer(self.id, interval.as_secs_f64() * 1e3, true))
+    }
+
+    /// Returns false if the timer has already fired or was cleared.
+    pub fn clear_timer(&mut self, handle: TimerHandle) -> bool {
+        host().event_loop_clear_timer(self.id, handle.0)
+    }
+
+    /// Starts delivering events of `categories`, a combination of `EVENTS_*` flags.
+    pub fn subscribe(&mut self, categories: u32) {
+        host().event_loop_subscribe(self.id, categories);
+    }
+
+    pub fn unsubscribe(&mut self, categories: u32) {
+        host().event_loop_unsubscribe(self.id, categories);
+    }
+
+    /*
+     *     Input events are listened for on a page element, the game's SVG
+     * by default, so that the game only gets keys while it's focused and
+     * doesn't take them from the rest of the page. Pointer coordinates are
+     * relative to the element. Returns false if no element matches the
+     * CSS `selector`, keeping the previous one.
+     */
+    pub fn set_target(&mut self, selector: &str) -> bool {
+        host().event_loop_set_target(self.id, selector)
+    }
+
+    /// Returns false if the loop has already been shut down.
+    pub fn shutdown(&mut self) -> bool {
+        host().event_loop_shutdown(self.id)
+    }
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell};
+    use std::rc::{Rc};
+    use crate::native_host;
+
+    // the native host delivers `Destroyed` when asked to, like demo.js does later
+    fn run_host() {
+        native_host::default_host().deliver_destroyed();
+    }
+
+    fn send_frame(event_loop: &EventLoop) {
+        event_loop_cb(event_loop.id, EVENT_ANIMATION_FRAME, 0, 0, 0, 0.0);
+    }
+
+    fn is_registered(id: u32) -> bool {
+        with_dispatcher(|d| d.loops.contains_key(&id))
+    }
+
+    // counts events, holding `token` to tell when the callback is dropped
+    fn counting_loop(token: &Rc<()>, frames: &Rc<Cell<u32>>, destroyed: &Rc<Cell<u32>>) -> EventLoop {
+        let (token, frames, destroyed) = (token.clone(), frames.clone(), destroyed.clone());
+        EventLoop::new(Box::new(move |event, _| {
+            let _ = &token;
+            match event {
+                Event::AnimationFrame { .. } => frames.set(frames.get() + 1),
+                Event::Destroyed => destroyed.set(destroyed.get() + 1),
+                _ => {},
+            }
+        }))
+    }
+
+    #[test]
+    fn drop_destroys_once_and_frees_callback() {
+        let (token, frames, destroyed) = (Rc::new(()), Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let event_loop = counting_loop(&token, &frames, &destroyed);
+        let id = event_loop.id;
+        send_frame(&event_loop);
+        assert_eq!(frames.get(), 1);
+
+        drop(event_loop);
+        assert_eq!(destroyed.get(), 0);
+        run_host();
+        run_host();
+        assert_eq!(destroyed.get(), 1);
+        assert!(!is_registered(id));
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn shutdown_twice_destroys_once() {
+        let (token, frames, destroyed) = (Rc::new(()), Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let mut event_loop = counting_loop(&token, &frames, &destroyed);
+        assert!(event_loop.shutdown());
+        assert!(!event_loop.shutdown());
+        drop(event_loop);
+        run_host();
+        assert_eq!(destroyed.get(), 1);
+    }
+
+    #[test]
+    fn shutdown_from_callback() {
+        let destroyed = Rc::new(Cell::new(0));
+        let counter = destroyed.clone();
+        let event_loop = EventLoop::new(Box::new(move |event, event_loop| {
+            match event {
+                Event::AnimationFrame { .. } => { event_loop.shutdown(); },
+                Event::Destroyed => counter.set(counter.get() + 1),
+                _ => {},
+            }
+        }));
+        let id = event_loop.id;
+        send_frame(&event_loop);
+        // the loop the callback got must not shut down on its own
+        assert!(is_registered(id));
+        run_host();
+        assert_eq!(destroyed.get(), 1);
+        assert!(!is_registered(id));
+        drop(event_loop);
+        run_host();
+        assert_eq!(destroyed.get(), 1);
+    }
+
+    #[test]
+    fn events_after_destroy_are_ignored() {
+        let (token, frames, destroyed) = (Rc::new(()), Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let mut event_loop = counting_loop(&token, &frames, &destroyed);
+        event_loop.shutdown();
+        run_host();
+        send_frame(&event_loop);
+        assert_eq!(frames.get(), 0);
+        assert_eq!(destroyed.get(), 1);
+    }
+
+    #[test]
+    fn restart_with_a_new_loop() {
+        let (token, frames, destroyed) = (Rc::new(()), Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let old = counting_loop(&token, &frames, &destroyed);
+        drop(old);
+        let (new_frames, new_destroyed) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let new = counting_loop(&token, &new_frames, &new_destroyed);
+        // the old loop is destroyed after the new one has started
+        run_host();
+        send_frame(&new);
+        assert_eq!(destroyed.get(), 1);
+        assert_eq!((new_frames.get(), new_destroyed.get()), (1, 0));
+        assert_eq!(Rc::strong_count(&token), 2);
+    }
+
+    // records which handler got which event
+    fn logging_handler(log: &Rc<RefCell<Vec<(u32, &'static str)>>>, n: u32) -> EventLoopCb {
+        let log = log.clone();
+        Box::new(move |event, _| {
+            let name = match event {
+                Event::AnimationFrame { .. } => "frame",
+                Event::Focus => "focus",
+                Event::Destroyed => "destroyed",
+                _ => "other",
+            };
+            log.borrow_mut().push((n, name));
+        })
+    }
+
+    #[test]
+    fn handlers_are_called_in_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut event_loop = EventLoop::new(logging_handler(&log, 1));
+        event_loop.add_handler(logging_handler(&log, 2));
+        send_frame(&event_loop);
+        event_loop.shutdown();
+        run_host();
+        assert_eq!(*log.borrow(), vec![(1, "frame"), (2, "frame"), (1, "destroyed"), (2, "destroyed")]);
+    }
+
+    #[test]
+    fn handler_added_during_dispatch_gets_next_event() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let handler_log = log.clone();
+        let mut added = false;
+        let event_loop = EventLoop::new(Box::new(move |_, event_loop| {
+            if !added {
+                added = true;
+                event_loop.add_handler(logging_handler(&handler_log, 2));
+            }
+            handler_log.borrow_mut().push((1, "any"));
+        }));
+        send_frame(&event_loop);
+        send_frame(&event_loop);
+        assert_eq!(*log.borrow(), vec![(1, "any"), (1, "any"), (2, "frame")]);
+    }
+
+    #[test]
+    fn handler_removed_during_dispatch_is_not_called() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let second = Rc::new(Cell::new(None));
+        let to_remove = second.clone();
+        let mut event_loop = EventLoop::new(Box::new(move |_, event_loop| {
+            if let Some(handler) = to_remove.take() {
+                assert!(event_loop.remove_handler(handler));
+            }
+        }));
+        second.set(Some(event_loop.add_handler(logging_handler(&log, 2))));
+        let third = event_loop.add_handler(logging_handler(&log, 3));
+        send_frame(&event_loop);
+        assert_eq!(*log.borrow(), vec![(3, "frame")]);
+        assert!(event_loop.remove_handler(third));
+        assert!(!event_loop.remove_handler(third));
+        send_frame(&event_loop);
+        assert_eq!(log.borrow().len(), 1);
+    }
+
+    #[test]
+    fn handler_may_remove_itself() {
+        let token = Rc::new(());
+        let handler_token = token.clone();
+        let own_id = Rc::new(Cell::new(None));
+        let handler_id = own_id.clone();
+        let mut event_loop = EventLoop::new(Box::new(|_, _| {}));
+        own_id.set(Some(event_loop.add_handler(Box::new(move |_, event_loop| {
+            let _ = &handler_token;
+            event_loop.remove_handler(handler_id.get().unwrap());
+        }))));
+        send_frame(&event_loop);
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn events_sent_from_a_handler_are_queued() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let handler_log = log.clone();
+        let event_loop = EventLoop::new(Box::new(move |event, event_loop| {
+            if let Event::AnimationFrame { .. } = event {
+                // like a host dispatching an event synchronously
+                event_loop_cb(event_loop.id, EVENT_FOCUS, 0, 0, 0, 0.0);
+                handler_log.borrow_mut().push((1, "frame"));
+            } else {
+                handler_log.borrow_mut().push((1, "focus"));
+            }
+        }));
+        send_frame(&event_loop);
+        assert_eq!(*log.borrow(), vec![(1, "frame"), (1, "focus")]);
+    }
+
+    #[test]
+    fn loops_created_and_shut_down_from_a_handler() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let handler_log = log.clone();
+        let mut created = None;
+        let event_loop = EventLoop::new(Box::new(move |_, event_loop| {
+            match created.take() {
+                None => created = Some(EventLoop::new(logging_handler(&handler_log, 2))),
+                Some(mut other) => {
+                    send_frame(&other);
+                    other.shutdown();
+                    event_loop.shutdown();
+                },
+            }
+        }));
+        send_frame(&event_loop);
+        send_frame(&event_loop);
+        run_host();
+        assert_eq!(*log.borrow(), vec![(2, "frame"), (2, "destroyed")]);
+        assert!(!is_registered(event_loop.id));
+    }
+
+    // payload pointers are 32 bit, so tests decode payloads without passing them through the host
+    #[test]
+    fn payload_events_are_decoded() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&2u32.to_le_bytes());
+        for &(id, x, y) in [(1u32, 10i32, -20i32), (2, 30, 40)].iter() {
+            buf.extend_from_slice(&id.to_le_bytes());
+            buf.extend_from_slice(&x.to_le_bytes());
+            buf.extend_from_slice(&y.to_le_bytes());
+        }
+        match read_payload_event(EVENT_TOUCH_START, Payload::from_bytes(buf), 0, 5.0) {
+            Some(Event::TouchStart { touches, time }) => {
+                assert_eq!(touches, vec![Touch { id: 1, x: 10, y: -20 }, Touch { id: 2, x: 30, y: 40 }]);
+                assert_eq!(time, 5.0);
+            },
+            _ => panic!("expected TouchStart"),
+        }
+    }
+
+    #[test]
+    fn pointer_kind_is_unpacked_from_the_id() {
+        assert_eq!(unpack_pointer(7), (7, PointerKind::Mouse));
+        assert_eq!(unpack_pointer(1 << 24 | 0xff_ffff), (0xff_ffff, PointerKind::Pen));
+        assert_eq!(unpack_pointer(2 << 24 | 5), (5, PointerKind::Touch));
+        // unknown kinds are treated as a mouse
+        assert_eq!(unpack_pointer(0xff << 24 | 5), (5, PointerKind::Mouse));
     }
 
-    pub fn shutdown(&mut self) {
-        unsafe { event_loop_shutdown(self.id); }
+    #[test]
+    fn malformed_payloads_are_dropped() {
+        // a string longer than the payload
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&100u32.to_le_bytes());
+        buf.extend_from_slice(b"KeyA");
+        assert!(read_payload_event(EVENT_KEY_DOWN, Payload::from_bytes(buf), 0, 0.0).is_none());
+        assert!(read_payload_event(EVENT_TEXT_INPUT, unsafe { Payload::from_host(0, 0) }, 0, 0.0).is_none());
     }
 }
@@ -0,0 +1,445 @@
+use std::cell::{RefCell};
+use std::collections::{HashMap};
+use std::future::{Future};
+use std::pin::{Pin};
+use std::rc::{Rc};
+use std::sync::{Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration};
+use crate::eventloop::{Event, EventLoop, EventLoopCb, TimerHandle};
+use crate::keycode::{KeyCode};
+
+/*
+ *     A single-threaded executor driven by event loop events. Scripts, e.g.
+ * the game flow between levels, are written as `async fn`s and wait for
+ * things with the futures below. Futures are polled from the event loop
+ * callback, after the event they're waiting for was delivered, so a script
+ * runs in between events and never concurrently with the rest of the game.
+ *
+ *     The futures only work inside tasks spawned on an `Executor`, the
+ * executor tells them what has happened through its `Reactor`.
+ */
+struct SleepState {
+    // the host timer is started by the executor after the sleep is first polled
+    timer: Option<TimerHandle>,
+    fired: bool,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct Reactor {
+    frames: u64,
+    frame_time: f64,
+    frame_wakers: Vec<Waker>,
+    keys: u64,
+    last_key: Option<KeyCode>,
+    key_wakers: Vec<Waker>,
+    next_sleep: u64,
+    sleeps: HashMap<u64, SleepState>,
+    // sleeps that need a host timer
+    new_sleeps: Vec<(u64, Duration)>,
+    timers: HashMap<TimerHandle, u64>,
+    // timers of sleeps dropped before they fired
+    cancelled: Vec<TimerHandle>,
+}
+
+thread_local! {
+    // the reactor of the executor that is polling tasks right now
+    static CURRENT: RefCell<Option<Rc<RefCell<Reactor>>>> = const { RefCell::new(None) };
+}
+
+fn current_reactor() -> Rc<RefCell<Reactor>> {
+    CURRENT.with(|current| current.borrow().clone())
+        .expect("future polled outside of an executor")
+}
+
+fn add_waker(wakers: &mut Vec<Waker>, waker: &Waker) {
+    if !wakers.iter().any(|w| w.will_wake(waker)) {
+        wakers.push(waker.clone());
+    }
+}
+
+fn wake_all(wakers: &mut Vec<Waker>) {
+    for waker in wakers.drain(..) {
+        waker.wake();
+    }
+}
+
+/// Resolves to the timestamp of the next animation frame.
+pub fn next_frame() -> NextFrame {
+    NextFrame { after: None }
+}
+
+pub struct NextFrame {
+    after: Option<u64>,
+}
+
+impl Future for NextFrame {
+    type Output = f64;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<f64> {
+        let reactor = current_reactor();
+        let mut reactor = reactor.borrow_mut();
+        let after = *self.after.get_or_insert(reactor.frames);
+        if reactor.frames > after {
+            return Poll::Ready(reactor.frame_time);
+        }
+        add_waker(&mut reactor.frame_wakers, cx.waker());
+        Poll::Pending
+    }
+}
+
+/// Resolves to the code of the next key pressed, key repeats are skipped.
+pub fn next_key() -> NextKey {
+    NextKey { after: None }
+}
+
+pub struct NextKey {
+    after: Option<u64>,
+}
+
+impl Future for NextKey {
+    type Output = KeyCode;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<KeyCode> {
+        let reactor = current_reactor();
+        let mut reactor = reactor.borrow_mut();
+        let after = *self.after.get_or_insert(reactor.keys);
+        if reactor.keys > after {
+            if let Some(code) = reactor.last_key {
+                return Poll::Ready(code);
+            }
+        }
+        add_waker(&mut reactor.key_wakers, cx.waker());
+        Poll::Pending
+    }
+}
+
+/// Resolves after `duration`, measured by a host timer.
+/// The timer is cleared if the future is dropped before that.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        duration: duration,
+        started: None,
+    }
+}
+
+pub struct Sleep {
+    duration: Duration,
+    started: Option<(Rc<RefCell<Reactor>>, u64)>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let duration = self.duration;
+        let (reactor, id) = self.started.get_or_insert_with(|| {
+            let reactor = current_reactor();
+            let id = {
+                let mut reactor = reactor.borrow_mut();
+                let id = reactor.next_sleep;
+                reactor.next_sleep += 1;
+                reactor.sleeps.insert(id, SleepState { timer: None, fired: false, waker: None });
+                reactor.new_sleeps.push((id, duration));
+                id
+            };
+            (reactor, id)
+        });
+        let mut reactor = reactor.borrow_mut();
+        let sleep = reactor.sleeps.get_mut(id).expect("sleep is registered until dropped");
+        if sleep.fired {
+            return Poll::Ready(());
+        }
+        sleep.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        let (reactor, id) = match self.started.take() {
+            Some(started) => started,
+            None => return,
+        };
+        let mut reactor = reactor.borrow_mut();
+        reactor.new_sleeps.retain(|&(new, _)| new != id);
+        if let Some(SleepState { timer: Some(timer), fired: false, .. }) = reactor.sleeps.remove(&id) {
+            reactor.timers.remove(&timer);
+            reactor.cancelled.push(timer);
+        }
+    }
+}
+
+struct TaskWaker {
+    woken: AtomicBool,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    waker: Arc<TaskWaker>,
+}
+
+impl Task {
+    fn new<F>(future: F) -> Task
+        where F: Future<Output = ()> + 'static
+    {
+        Task {
+            future: Box::pin(future),
+            // new tasks are polled right away
+            waker: Arc::new(TaskWaker { woken: AtomicBool::new(true) }),
+        }
+    }
+}
+
+/// Spawns tasks onto an executor, also from inside its tasks.
+#[derive(Clone)]
+pub struct Spawner {
+    spawned: Rc<RefCell<Vec<Task>>>,
+}
+
+impl Spawner {
+    pub fn spawn<F>(&self, future: F)
+        where F: Future<Output = ()> + 'static
+    {
+        self.spawned.borrow_mut().push(Task::new(future));
+    }
+}
+
+#[derive(Default)]
+pub struct Executor {
+    reactor: Rc<RefCell<Reactor>>,
+    tasks: Vec<Task>,
+    spawned: Rc<RefCell<Vec<Task>>>,
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        Executor::default()
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        Spawner { spawned: self.spawned.clone() }
+    }
+
+    /// Tasks start running on the next event.
+    pub fn spawn<F>(&self, future: F)
+        where F: Future<Output = ()> + 'static
+    {
+        self.spawner().spawn(future);
+    }
+
+    /// Wakes the tasks waiting for `event`, and runs tasks until they're all waiting.
+    pub fn handle_event(&mut self, event: &Event, event_loop: &mut EventLoop) {
+        {
+            let mut reactor = self.reactor.borrow_mut();
+            let reactor = &mut *reactor;
+            match *event {
+                Event::Destroyed => {},
+                Event::AnimationFrame { time } => {
+                    reactor.frames += 1;
+                    reactor.frame_time = time;
+                    wake_all(&mut reactor.frame_wakers);
+                },
+                Event::KeyDown { code, repeat: false, .. } => {
+                    reactor.keys += 1;
+                    reactor.last_key = Some(code);
+                    wake_all(&mut reactor.key_wakers);
+                },
+                Event::Timer { handle, .. } => {
+                    let sleep = reactor.timers.remove(&handle)
+                        .and_then(|id| reactor.sleeps.get_mut(&id));
+                    if let Some(sleep) = sleep {
+                        sleep.fired = true;
+                        if let Some(waker) = sleep.waker.take() {
+                            waker.wake();
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        if let Event::Destroyed = *event {
+            // the loop is gone, tasks would wait forever
+            self.tasks.clear();
+            self.spawned.borrow_mut().clear();
+            return;
+        }
+        self.run_until_stalled();
+        self.start_timers(event_loop);
+    }
+
+    fn run_until_stalled(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = Some(self.reactor.clone()));
+        loop {
+            let spawned = ::std::mem::take(&mut *self.spawned.borrow_mut());
+            self.tasks.extend(spawned);
+            let mut progress = false;
+            let mut i = 0;
+            while i < self.tasks.len() {
+                if !self.tasks[i].waker.woken.swap(false, Ordering::SeqCst) {
+                    i += 1;
+                    continue;
+                }
+                progress = true;
+                let waker = Waker::from(self.tasks[i].waker.clone());
+                let mut cx = Context::from_waker(&waker);
+                match self.tasks[i].future.as_mut().poll(&mut cx) {
+                    Poll::Ready(()) => { self.tasks.remove(i); },
+                    Poll::Pending => i += 1,
+                }
+            }
+            if !progress && self.spawned.borrow().is_empty() {
+                break;
+            }
+        }
+        CURRENT.with(|current| *current.borrow_mut() = None);
+    }
+
+    // tells the host about what the tasks are waiting for
+    fn start_timers(&mut self, event_loop: &mut EventLoop) {
+        let (new_sleeps, cancelled, wants_frame) = {
+            let mut reactor = self.reactor.borrow_mut();
+            (::std::mem::take(&mut reactor.new_sleeps),
+             ::std::mem::take(&mut reactor.cancelled),
+             !reactor.frame_wakers.is_empty())
+        };
+        for timer in cancelled {
+            event_loop.clear_timer(timer);
+        }
+        for (id, duration) in new_sleeps {
+            let timer = event_loop.set_timeout(duration);
+            let mut reactor = self.reactor.borrow_mut();
+            let reactor = &mut *reactor;
+            if let Some(sleep) = reactor.sleeps.get_mut(&id) {
+                sleep.timer = Some(timer);
+                reactor.timers.insert(timer, id);
+            }
+        }
+        if wants_frame {
+            event_loop.request_animation_frame();
+        }
+    }
+
+    /// An event loop callback running this executor.
+    pub fn into_handler(mut self) -> EventLoopCb {
+        Box::new(move |event, event_loop| self.handle_event(&event, event_loop))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell};
+
+    // events are fed to the executor directly, the loop only talks to the mock host
+    fn test_loop() -> EventLoop {
+        EventLoop::new(Box::new(|_, _| {}))
+    }
+
+    fn frame(time: f64) -> Event {
+        Event::AnimationFrame { time: time }
+    }
+
+    fn key_down(code: KeyCode, repeat: bool) -> Event {
+        Event::KeyDown {
+            code: code,
+            key: String::new(),
+            modifiers: Default::default(),
+            repeat: repeat,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn tasks_wait_for_frames() {
+        let mut event_loop = test_loop();
+        let mut executor = Executor::new();
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let log = times.clone();
+        executor.spawn(async move {
+            for _ in 0..2 {
+                let time = next_frame().await;
+                log.borrow_mut().push(time);
+            }
+        });
+        executor.handle_event(&Event::Focus, &mut event_loop);
+        assert!(times.borrow().is_empty());
+        executor.handle_event(&frame(16.0), &mut event_loop);
+        executor.handle_event(&frame(32.0), &mut event_loop);
+        executor.handle_event(&frame(48.0), &mut event_loop);
+        assert_eq!(*times.borrow(), vec![16.0, 32.0]);
+        assert!(executor.tasks.is_empty());
+    }
+
+    #[test]
+    fn next_key_skips_repeats() {
+        let mut event_loop = test_loop();
+        let mut executor = Executor::new();
+        let pressed = Rc::new(Cell::new(None));
+        let result = pressed.clone();
+        executor.spawn(async move {
+            result.set(Some(next_key().await));
+        });
+        executor.handle_event(&frame(0.0), &mut event_loop);
+        executor.handle_event(&key_down(KeyCode::KeyA, true), &mut event_loop);
+        assert_eq!(pressed.get(), None);
+        executor.handle_event(&key_down(KeyCode::KeyB, false), &mut event_loop);
+        assert_eq!(pressed.get(), Some(KeyCode::KeyB));
+    }
+
+    #[test]
+    fn sleep_waits_for_its_timer() {
+        let mut event_loop = test_loop();
+        let mut executor = Executor::new();
+        let done = Rc::new(Cell::new(false));
+        let flag = done.clone();
+        executor.spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            flag.set(true);
+        });
+        executor.handle_event(&frame(0.0), &mut event_loop);
+        let timer = *executor.reactor.borrow().timers.keys().next().expect("timer started");
+        executor.handle_event(&frame(16.0), &mut event_loop);
+        assert!(!done.get());
+        executor.handle_event(&Event::Timer { handle: timer, time: 100.0 }, &mut event_loop);
+        assert!(done.get());
+        assert!(executor.reactor.borrow().sleeps.is_empty());
+    }
+
+    #[test]
+    fn dropped_sleep_cancels_its_timer() {
+        let mut event_loop = test_loop();
+        let mut executor = Executor::new();
+        executor.spawn(async {
+            sleep(Duration::from_secs(10)).await;
+        });
+        executor.handle_event(&frame(0.0), &mut event_loop);
+        assert_eq!(executor.reactor.borrow().timers.len(), 1);
+        executor.handle_event(&Event::Destroyed, &mut event_loop);
+        let reactor = executor.reactor.borrow();
+        assert!(reactor.timers.is_empty() && reactor.sleeps.is_empty());
+        assert_eq!(reactor.cancelled.len(), 1);
+    }
+
+    #[test]
+    fn tasks_spawned_by_tasks_run_in_the_same_event() {
+        let mut event_loop = test_loop();
+        let mut executor = Executor::new();
+        let spawner = executor.spawner();
+        let ran = Rc::new(Cell::new(false));
+        let flag = ran.clone();
+        executor.spawn(async move {
+            spawner.spawn(async move { flag.set(true) });
+        });
+        executor.handle_event(&Event::Focus, &mut event_loop);
+        assert!(ran.get());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::executor::{next_frame};
+use crate::game::{Game, GameState};
+use crate::with_game;
+
+/*
+ *     Game flow scripts, run by the executor on the game's event loop.
+ * They return `None` as soon as the game is gone, e.g. stopped by the page.
+ */
+const BANNER_BLINKS: u32 = 4;
+// seconds of game time, like the durations in `Config`
+const BANNER_BLINK: f64 = 0.25;
+
+// checks `f` on every frame until it returns true
+async fn wait_until<F>(mut f: F) -> Option<()>
+    where F: FnMut(&mut Game) -> bool
+{
+    loop {
+        next_frame().await;
+        if with_game(&mut f)? {
+            return Some(());
+        }
+    }
+}
+
+/*
+ *     Waits for `seconds` of game time rather than of the host's clock, so
+ * that it stops along with the game, e.g. while the page is hidden or
+ * a key is being rebound.
+ */
+async fn wait_game_time(seconds: f64) -> Option<()> {
+    let end = with_game(|game| game.tick + (seconds / game.config.delta_t).round() as u64)?;
+    wait_until(|game| game.tick >= end).await
+}
+
+/*
+ *     When a level is cleared the number of the next level blinks in the
+ * middle of the field, then stays there until the player presses fire.
+ * A cleared level is a menu screen, where fire is `Confirm`: Space, the A
+ * button and the touch fire button all confirm there, and since switching
+ * contexts releases everything, holding fire through the end of the level
+ * doesn't skip the intro. Levels are numbered from 1, which has 2
+ * asteroids, so the next level's number is the number of asteroids in the
+ * cleared one.
+ */
+async fn level_intro() -> Option<()> {
+    wait_until(|game| game.game_state == GameState::LevelCleared).await?;
+    let number = with_game(|game| game.level)?;
+    for i in 0..BANNER_BLINKS * 2 {
+        with_game(|game| game.banner = if i % 2 == 0 { Some(number) } else { None })?;
+        wait_game_time(BANNER_BLINK).await?;
+    }
+    // presses during the blinking don't count
+    with_game(|game| {
+        game.banner = Some(number);
+        game.confirmed = false;
+    })?;
+    wait_until(|game| game.confirmed).await?;
+    with_game(|game| game.start_next_level())
+}
+
+pub async fn game_flow() {
+    while level_intro().await.is_some() {}
+}
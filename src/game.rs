@@ -1,8 +1,15 @@
-use ::math::{Vec2D};
-use ::ship::{Ship};
-pub use ::input::{Inputs, InputIndex};
-use ::geom::{test_circle_point, test_circle_triangle};
-use ::rng::{StdRng, Rng, new_rng};
+use crate::math::{Vec2D};
+use crate::ship::{Ship};
+pub use crate::input::{Inputs, InputIndex, InputContext, ControlScheme, AxisIndex, AxisConfig, ResponseCurve};
+use crate::keybinds::{KeyBinds};
+use crate::keycode::{KeyCode, KeyNames, Modifiers};
+use crate::gamepad::{GamepadBinds, Rumble};
+use crate::touch::{TouchControls};
+use crate::viewport::{Viewport};
+use crate::geom::{test_circle_point, test_circle_triangle};
+use crate::numeric::{MathBackend, StdMath, SoftMath};
+pub use crate::numeric::{MathMode};
+use crate::rng::{GameRng, Rng, new_rng, seeded_rng};
 
 pub struct Config {
     pub acceleration: f64,
@@ -14,6 +21,8 @@ pub struct Config {
     pub angular_accel: f64,
     pub angular_limit: f64,
     pub angular_drag: f64,
+    // angular speed to turn with, per radian off the mouse cursor
+    pub aim_gain: f64,
 
     pub bullet_interval: f64,
     pub bullet_speed: f64,
@@ -23,17 +32,25 @@ pub struct Config {
 
     pub asteroid_min_size: f64,
 
+    pub death_rumble: Rumble,
+    pub explosion_rumble: Rumble,
+
     pub field_size: Vec2D,
-    pub key_binds: Vec<(u32, InputIndex)>,
+    // `Deterministic` plays the same on every target, for replays and checksums
+    pub math: MathMode,
+    pub control_scheme: ControlScheme,
+    pub axes: [AxisConfig; AxisIndex::_NumberOfAxes as usize],
+    pub key_binds: KeyBinds,
+    pub key_names: KeyNames,
+    pub gamepad_binds: GamepadBinds,
+    pub mouse_binds: Vec<(u32, InputIndex)>,
 }
 
-const DEFAULT_KEYBINDS: &[(u32, InputIndex)] = &[
-    (90, InputIndex::Shoot),    // Z
-    (32, InputIndex::Shoot),    // Space
-    (38, InputIndex::Forward),  // Up
-    (40, InputIndex::Backward), // Down
-    (37, InputIndex::Left),     // Left
-    (39, InputIndex::Right),    // Right
+const DEFAULT_MOUSE_BINDS: &[(u32, InputIndex)] = &[
+    (0, InputIndex::Shoot),     // Left button
+    (2, InputIndex::Forward),   // Right button
+    (0, InputIndex::Confirm),   // Left button
+    (0, InputIndex::Resume),    // Left button
 ];
 
 impl Config {
@@ -48,6 +65,7 @@ impl Config {
             angular_accel: 30.0,
             angular_limit: 4.0,
             angular_drag: 8.0,
+            aim_gain: 10.0,
 
             bullet_interval: 0.15,
             bullet_speed: 400.0,
@@ -55,25 +73,41 @@ impl Config {
 
             asteroid_min_size: 20.0,
 
+            death_rumble: Rumble { strong: 1.0, weak: 1.0, duration: 0.4 },
+            explosion_rumble: Rumble { strong: 0.0, weak: 0.5, duration: 0.1 },
+
             delta_t: 1.0 / 60.0,
             field_size: Vec2D { x: 1280.0, y: 720.0 },
-            key_binds: DEFAULT_KEYBINDS.to_vec(),
+            math: MathMode::Native,
+            control_scheme: ControlScheme::Keyboard,
+            axes: [
+                // Rotate
+                AxisConfig { dead_zone: 0.15, curve: ResponseCurve::Quadratic },
+                // Thrust
+                AxisConfig { dead_zone: 0.1, curve: ResponseCurve::Linear },
+            ],
+            key_binds: KeyBinds::new(),
+            key_names: KeyNames::new(),
+            gamepad_binds: GamepadBinds::new(),
+            mouse_binds: DEFAULT_MOUSE_BINDS.to_vec(),
         }
     }
 
-    pub fn lookup_input_key(&self, code: u32) -> Option<InputIndex> {
-        for &(key, val) in self.key_binds.iter() {
-            if key == code {
-                return Some(val);
-            }
-        }
-        None
+    pub fn lookup_input_key(&self, code: KeyCode, context: InputContext) -> Option<InputIndex> {
+        self.key_binds.lookup(code, context)
+    }
+
+    pub fn lookup_mouse_button(&self, button: u32, context: InputContext) -> Option<InputIndex> {
+        self.mouse_binds.iter()
+            .find(|&&(bound, input)| bound == button && input.context() == context)
+            .map(|&(_, input)| input)
     }
 }
 
 #[derive(PartialEq)]
 pub enum BulletSource {
     Player,
+    #[allow(dead_code)]
     UFO,
 }
 
@@ -86,17 +120,16 @@ pub struct Bullet {
 }
 
 impl Bullet {
-    pub fn tick(&mut self, config: &Config) {
-        self.pos += self.speed.scale(config.delta_t);
-        self.pos.clip(&config.field_size);
+    pub fn tick<M: MathBackend>(&mut self, config: &Config) {
+        self.pos = M::wrap(self.pos + self.speed * config.delta_t, config.field_size);
     }
 }
 
 impl Bullet {
-    pub fn new(game: &Game, source: BulletSource) -> Bullet {
+    pub fn new<M: MathBackend>(game: &Game, source: BulletSource) -> Bullet {
         let ship = &game.ship;
         let config = &game.config;
-        let direction = Vec2D::one().rotate(ship.angle);
+        let direction = M::unit(ship.angle);
         Bullet {
             pos: ship.pos + direction.scale(20.0),
             speed: direction.scale(config.bullet_speed),
@@ -119,18 +152,17 @@ pub struct Asteroid {
 }
 
 impl Asteroid {
-    pub fn tick(&mut self, config: &Config) {
-        self.pos += self.speed.scale(config.delta_t);
-        self.pos.clip(&config.field_size);
+    pub fn tick<M: MathBackend>(&mut self, config: &Config) {
+        self.pos = M::wrap(self.pos + self.speed * config.delta_t, config.field_size);
         self.angle += self.angle_speed * config.delta_t;
     }
 
-    pub fn split_off(&self, config: &Config) -> Vec<Asteroid> {
+    pub fn split_off<M: MathBackend>(&self, config: &Config) -> Vec<Asteroid> {
         let mut rv = Vec::new();
         if self.size > config.asteroid_min_size {
             let mut copy0 = self.clone();
             let mut copy1 = self.clone();
-            let offset = Vec2D::one().rotate(self.angle).scale(self.size / 2.0);
+            let offset = M::unit(self.angle) * (self.size / 2.0);
             copy0.size /= 2.0;
             copy1.size /= 2.0;
             copy0.pos += offset;
@@ -170,7 +202,10 @@ impl Explosion {
 #[derive(Eq, PartialEq)]
 pub enum GameState {
     Running,
+    Paused,
     Respawning,
+    // waiting for the level flow script to start the next level
+    LevelCleared,
     GameOver,
 }
 
@@ -178,6 +213,7 @@ pub struct Game {
     pub game_state: GameState,
     pub ship: Ship,
     pub ufo: Option<UFO>,
+    #[allow(dead_code)]
     pub ufo_spawn_tick: u64,
     pub lives: u64,
     pub level: usize,
@@ -188,27 +224,46 @@ pub struct Game {
     pub bullets: Vec<Bullet>,
     pub asteroids: Vec<Asteroid>,
     pub inputs: Inputs,
+    pub touch: TouchControls,
+    pub viewport: Viewport,
+    pub rebinding: Option<InputIndex>,
+    // the page isn't visible, nobody is watching the game
+    pub hidden: bool,
+    // big number in the middle of the field, e.g. the next level
+    pub banner: Option<usize>,
+    // set when Confirm is pressed between levels, scripts reset it before waiting
+    pub confirmed: bool,
+    // text typed so far, while the page asks for text
+    pub text_entry: Option<String>,
+    pub entered_text: Option<String>,
+    pub rumble: Option<Rumble>,
     pub config: Config,
-    pub rng: StdRng,
+    pub rng: GameRng,
 }
 
 fn collide_asteroid_bullet(asteroid: &Asteroid, bullet: &Bullet) -> bool {
     test_circle_point(asteroid.pos, asteroid.size, bullet.pos)
 }
 
-fn collide_asteroid_ship(asteroid: &Asteroid, ship: &Ship) -> bool {
+fn collide_asteroid_ship<M: MathBackend>(asteroid: &Asteroid, ship: &Ship) -> bool {
+    let model = M::transform(ship.pos, ship.angle, 2.2);
     let tr: Vec<Vec2D> = [
         Vec2D { x: 10.0, y: 0.0 },
         Vec2D { x: -10.0, y: -5.0 },
         Vec2D { x: -10.0, y: 5.0 },
-    ].iter().map(|p| ship.pos + p.scale(2.2).rotate(ship.angle)).collect();
+    ].iter().map(|&p| model.apply(p)).collect();
     test_circle_triangle(asteroid.pos, asteroid.size, tr[0], tr[1], tr[2])
 }
 
-use ::std::f64::consts::PI;
+use std::f64::consts::PI;
 
 impl Game {
-    pub fn new() -> Game {
+    pub fn with_config(config: Config) -> Game {
+        Game::with_rng(config, new_rng().expect("could not seed rng"))
+    }
+
+    /// A game with a seeded `rng` plays the same way given the same input.
+    pub fn with_rng(config: Config, rng: GameRng) -> Game {
         let mut game = Game {
             tick: 0,
             lives: 4,
@@ -218,19 +273,35 @@ impl Game {
             next_bullet_tick: 0,
             ship: Ship::new(),
             ufo: None,
-            ufo_spawn_tick: ::std::u64::MAX,
+            ufo_spawn_tick: u64::MAX,
             explosions: Vec::new(),
             bullets: Vec::new(),
             asteroids: Vec::new(),
             inputs: Inputs::new(),
-            config: Config::new(),
-            rng: new_rng().expect("could not seed rng"),
+            touch: TouchControls::new(config.field_size),
+            viewport: Viewport::new(config.field_size),
+            rebinding: None,
+            hidden: false,
+            banner: None,
+            confirmed: false,
+            text_entry: None,
+            entered_text: None,
+            rumble: None,
+            config: config,
+            rng: rng,
         };
         game.spawn_level();
         game
     }
 
     pub fn spawn_level(&mut self) {
+        match self.config.math {
+            MathMode::Native => self.spawn_level_with::<StdMath>(),
+            MathMode::Deterministic => self.spawn_level_with::<SoftMath>(),
+        }
+    }
+
+    fn spawn_level_with<M: MathBackend>(&mut self) {
         let field_size = self.config.field_size;
         self.ship.pos = field_size.scale(0.5);
         self.ship.speed = Vec2D::zero();
@@ -243,12 +314,12 @@ impl Game {
                     x: field_size.x * self.rng.next_f64(),
                     y: field_size.y * self.rng.next_f64(),
                 };
-                if (pos - self.ship.pos).len() > 300.0 { break; }
+                if M::len(pos - self.ship.pos) > 300.0 { break; }
             }
             let angle = PI * 2.0 * self.rng.next_f64();
             self.asteroids.push(Asteroid {
                 pos: pos,
-                speed: Vec2D { x: 100.0, y: 0.0 }.rotate(angle),
+                speed: M::rotate(Vec2D { x: 100.0, y: 0.0 }, angle),
                 angle: angle,
                 angle_speed: 0.6,
                 size: 50.0,
@@ -258,18 +329,242 @@ impl Game {
         }
     }
 
+    pub fn start_next_level(&mut self) {
+        self.level += 1;
+        self.spawn_level();
+        self.game_state = GameState::Running;
+        self.banner = None;
+        self.update_input_context();
+    }
+
+    /// Does what a player would confirm right away, for games played
+    /// without one, like the simulation and agents. Call it before the tick.
+    pub fn skip_menus(&mut self) {
+        match self.game_state {
+            GameState::LevelCleared => self.start_next_level(),
+            GameState::Respawning if self.lives > 0 => {
+                self.inputs.action_down(InputIndex::Confirm);
+                self.inputs.action_up(InputIndex::Confirm);
+            },
+            _ => {},
+        }
+    }
+
     pub fn reset(&mut self) {
-        *self = Game::new();
+        let config = ::std::mem::replace(&mut self.config, Config::new());
+        let mut touch = ::std::mem::replace(&mut self.touch, TouchControls::new(config.field_size));
+        touch.forget_touches();
+        let viewport = self.viewport;
+        // keep a seeded game deterministic
+        let rng = ::std::mem::replace(&mut self.rng, seeded_rng(0));
+        *self = Game::with_rng(config, rng);
+        self.touch = touch;
+        self.viewport = viewport;
+    }
+
+    /*
+     *     A hash of the simulated state, FNV-1a over the exact bits. Games
+     * in `MathMode::Deterministic` given the same seed and input have the
+     * same checksums on every target, so replays and peers can compare them.
+     */
+    pub fn checksum(&self) -> u64 {
+        let ship = &self.ship;
+        let mut words = vec![
+            self.tick, self.score, self.lives, self.level as u64,
+            ship.pos.x.to_bits(), ship.pos.y.to_bits(), ship.speed.x.to_bits(), ship.speed.y.to_bits(),
+            ship.angle.to_bits(), ship.angular_speed.to_bits(), ship.dead as u64,
+        ];
+        for asteroid in self.asteroids.iter() {
+            words.extend_from_slice(&[
+                asteroid.pos.x.to_bits(), asteroid.pos.y.to_bits(),
+                asteroid.speed.x.to_bits(), asteroid.speed.y.to_bits(),
+                asteroid.angle.to_bits(), asteroid.size.to_bits(),
+            ]);
+        }
+        for bullet in self.bullets.iter() {
+            words.extend_from_slice(&[
+                bullet.pos.x.to_bits(), bullet.pos.y.to_bits(),
+                bullet.speed.x.to_bits(), bullet.speed.y.to_bits(),
+            ]);
+        }
+        words.iter()
+            .flat_map(|word| word.to_le_bytes())
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+    }
+
+    /// Makes the next key press bind to `input` instead of being played.
+    pub fn begin_rebind(&mut self, input: InputIndex) {
+        self.rebinding = Some(input);
+        self.inputs.release_all();
+    }
+
+    /// Completes the rebinding flow started by `begin_rebind`.
+    /// Escape cancels it. Returns true if key bindings have changed.
+    pub fn finish_rebind(&mut self, code: KeyCode) -> bool {
+        let input = match self.rebinding.take() {
+            Some(input) => input,
+            None => return false,
+        };
+        if code == KeyCode::Escape || code == KeyCode::Unidentified {
+            return false;
+        }
+        self.config.key_binds.bind(code, input);
+        true
+    }
+
+    /// Returns true only if the key finished rebinding an action, i.e. key
+    /// bindings have changed, not whether it pressed anything.
+    /// `key` is what the key types in the player's keyboard layout, keys
+    /// are only named after what they type without modifiers, e.g. Digit1
+    /// stays "1" after typing "!" with shift.
+    pub fn key_down(&mut self, code: KeyCode, key: &str, modifiers: Modifiers, repeat: bool, time: f64) -> bool {
+        if !modifiers.any() {
+            self.config.key_names.learn(code, key);
+        }
+        if self.rebinding.is_some() {
+            return self.finish_rebind(code);
+        }
+        // repeats would press keys held across a context switch again
+        if !repeat {
+            self.inputs.key_down(code, time, &self.config);
+        }
+        false
+    }
+
+    /// Text typed or pasted while text entry is active.
+    pub fn text_input(&mut self, input: &str) {
+        if let Some(text) = self.text_entry.as_mut() {
+            text.extend(input.chars().filter(|c| !c.is_control()));
+        }
+    }
+
+    pub fn begin_text_entry(&mut self) {
+        self.text_entry = Some(String::new());
+        self.entered_text = None;
+        self.update_input_context();
+    }
+
+    pub fn input_context(&self) -> InputContext {
+        if self.text_entry.is_some() {
+            return InputContext::TextEntry;
+        }
+        match self.game_state {
+            GameState::Running => InputContext::Gameplay,
+            GameState::Paused => InputContext::Paused,
+            GameState::Respawning | GameState::LevelCleared | GameState::GameOver => InputContext::Menu,
+        }
+    }
+
+    fn update_input_context(&mut self) {
+        let context = self.input_context();
+        self.inputs.set_context(context);
+    }
+
+    pub fn key_up(&mut self, code: KeyCode, time: f64) {
+        self.inputs.key_up(code, time, &self.config);
+    }
+
+    /// Fits the scene to a screen of `width` by `height` CSS pixels.
+    pub fn resize(&mut self, width: f64, height: f64, dpr: f64) {
+        // e.g. the page is being laid out, there's nothing to fit the scene into
+        if width <= 0.0 || height <= 0.0 {
+            return;
+        }
+        self.viewport = Viewport::fit(self.config.field_size, width, height, dpr);
+        self.touch.layout = self.viewport.layout;
+    }
+
+    /// Releases everything held, since keys and touches released
+    /// while the page is out of focus never send their events.
+    pub fn focus_lost(&mut self) {
+        self.inputs.release_all();
+        self.touch.release(&mut self.inputs, &self.config);
+    }
+
+    /*
+     *     The game pauses when the page is hidden, and stays paused when it's
+     * shown again, so that the player isn't thrown back into a running game.
+     */
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+        if hidden {
+            self.focus_lost();
+            if self.game_state == GameState::Running {
+                self.game_state = GameState::Paused;
+                self.update_input_context();
+            }
+        }
+    }
+
+    pub fn set_control_scheme(&mut self, scheme: ControlScheme) {
+        self.inputs.release_mouse(&self.config);
+        self.config.control_scheme = scheme;
+    }
+
+    /// Asks the host to rumble gamepads after this tick.
+    pub fn request_rumble(&mut self, rumble: Rumble) {
+        self.rumble = Some(match self.rumble {
+            Some(current) => current.stronger(rumble),
+            None => rumble,
+        });
+    }
+
+    /// Advances the game by one tick, `time` is the host timestamp of the frame.
+    pub fn tick(&mut self, time: f64) {
+        if self.rebinding.is_some() || self.hidden {
+            // the game is frozen while waiting for a key
+            return;
+        }
+
+        self.inputs.begin_tick(time, &self.config);
+
+        if self.text_entry.is_some() {
+            self.tick_text_entry();
+        } else {
+            self.tick_game();
+        }
+
+        // the state may have changed, keys from now on go to the new context
+        self.update_input_context();
+        // forget pressed inputs
+        self.inputs.tick();
+    }
+
+    fn tick_text_entry(&mut self) {
+        let inputs = &self.inputs;
+        if inputs.just_pressed(InputIndex::Erase) {
+            if let Some(text) = self.text_entry.as_mut() {
+                text.pop();
+            }
+        }
+        if inputs.just_pressed(InputIndex::Submit) {
+            self.entered_text = self.text_entry.take();
+        } else if inputs.just_pressed(InputIndex::Cancel) {
+            self.text_entry = None;
+        }
     }
 
-    pub fn tick(&mut self) {
+    fn tick_game(&mut self) {
+        match self.config.math {
+            MathMode::Native => self.tick_game_with::<StdMath>(),
+            MathMode::Deterministic => self.tick_game_with::<SoftMath>(),
+        }
+    }
+
+    fn tick_game_with<M: MathBackend>(&mut self) {
         match self.game_state {
             GameState::GameOver => {
-                if self.inputs.is_down(InputIndex::Shoot) {
+                if self.inputs.just_pressed(InputIndex::Confirm) {
                     self.reset();
                 }
                 return;
             },
+            GameState::Paused => {
+                if self.inputs.just_pressed(InputIndex::Resume) {
+                    self.game_state = GameState::Running;
+                }
+                return;
+            },
             GameState::Respawning => {
                 if self.lives == 0 {
                     self.game_state = GameState::GameOver;
@@ -277,7 +572,7 @@ impl Game {
                 }
 
                 let ship = &mut self.ship;
-                if self.inputs.is_down(InputIndex::Shoot) {
+                if self.inputs.just_pressed(InputIndex::Confirm) {
                     self.game_state = GameState::Running;
                     self.lives -= 1;
                     ship.speed = Vec2D::zero();
@@ -287,9 +582,19 @@ impl Game {
                 }
             },
             GameState::Running => {
+                if self.inputs.just_pressed(InputIndex::Pause) {
+                    self.game_state = GameState::Paused;
+                    return;
+                }
                 if self.asteroids.len() == 0 {
-                    self.level += 1;
-                    self.spawn_level();
+                    self.game_state = GameState::LevelCleared;
+                    self.confirmed = false;
+                }
+            },
+            // bullets and explosions keep going while the flow script runs
+            GameState::LevelCleared => {
+                if self.inputs.just_pressed(InputIndex::Confirm) {
+                    self.confirmed = true;
                 }
             },
         }
@@ -306,12 +611,12 @@ impl Game {
             let inputs = &self.inputs;
             let config = &self.config;
             let ship = &mut self.ship;
-            ship.tick(inputs, config);
+            ship.tick::<M>(inputs, config);
             for asteroid in self.asteroids.iter_mut() {
-                asteroid.tick(config);
+                asteroid.tick::<M>(config);
             }
             for bullet in self.bullets.iter_mut() {
-                bullet.tick(config);
+                bullet.tick::<M>(config);
             }
             for ufo in self.ufo.iter_mut() {
                 ufo.tick();
@@ -324,12 +629,15 @@ impl Game {
             let config = &self.config;
             if inputs.been_pressed(InputIndex::Shoot) && self.tick >= self.next_bullet_tick {
                 self.next_bullet_tick = self.tick + (config.bullet_interval / config.delta_t) as u64;
-                let bullet = Bullet::new(self, BulletSource::Player);
+                let bullet = Bullet::new::<M>(self, BulletSource::Player);
                 self.bullets.push(bullet);
             }
         }
 
 
+        let ship_was_dead = self.ship.dead;
+        let explosion_count = self.explosions.len();
+
         // COLLISIONS
         {
             // collide asteroids with bullets
@@ -347,8 +655,8 @@ impl Game {
                     if collide_asteroid_bullet(asteroid, bullet) {
                         if !asteroid.dead {
                             score_change += 100;
-                            new_asteroids.append(&mut asteroid.split_off(&config));
-                            new_explosions.push(Explosion::new(asteroid.pos, tick, &config));
+                            new_asteroids.append(&mut asteroid.split_off::<M>(config));
+                            new_explosions.push(Explosion::new(asteroid.pos, tick, config));
                         }
                         asteroid.dead = true;
                         bullet.dead = true;
@@ -385,7 +693,7 @@ impl Game {
                         }
                     },
                     BulletSource::Player => {
-                        if ufo.as_ref().map_or(false, |ufo| collide_ufo_bullet(ufo, bullet)) {
+                        if ufo.as_ref().is_some_and(|ufo| collide_ufo_bullet(ufo, bullet)) {
                             *ufo = None;
                             bullet.dead = true;
                         }
@@ -411,24 +719,22 @@ impl Game {
             for asteroid in asteroids.iter_mut() {
                 let mut collided = false;
 
-                if !ship.dead && collide_asteroid_ship(asteroid, ship) {
+                if !ship.dead && collide_asteroid_ship::<M>(asteroid, ship) {
                     self.game_state = GameState::Respawning;
-                    explosions.push(Explosion::new(ship.pos, tick, &config));
-                    explosions.push(Explosion::new(asteroid.pos, tick, &config));
+                    explosions.push(Explosion::new(ship.pos, tick, config));
+                    explosions.push(Explosion::new(asteroid.pos, tick, config));
                     ship.dead = true;
                     collided = true;
                 }
 
-                if ufo.as_ref().map_or(false, |ufo| collide_asteroid_ufo(asteroid, ufo)) {
+                if ufo.as_ref().is_some_and(|ufo| collide_asteroid_ufo(asteroid, ufo)) {
                     *ufo = None;
                     collided = true;
                 }
 
-                if collided {
-                    if !asteroid.dead {
-                        new_asteroids.append(&mut asteroid.split_off(&config));
-                        asteroid.dead = true;
-                    }
+                if collided && !asteroid.dead {
+                    new_asteroids.append(&mut asteroid.split_off::<M>(config));
+                    asteroid.dead = true;
                 }
             }
 
@@ -443,7 +749,151 @@ impl Game {
         }
         // END COLLISIONS
 
-        // forget pressed inputs
-        self.inputs.tick();
+        if self.explosions.len() > explosion_count {
+            let rumble = self.config.explosion_rumble;
+            self.request_rumble(rumble);
+        }
+        if self.ship.dead && !ship_was_dead {
+            let rumble = self.config.death_rumble;
+            self.request_rumble(rumble);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_MS: f64 = 1000.0 / 60.0;
+
+    struct TestGame {
+        game: Game,
+        time: f64,
+    }
+
+    impl TestGame {
+        fn new() -> TestGame {
+            TestGame {
+                game: Game::with_config(Config::new()),
+                time: 0.0,
+            }
+        }
+
+        fn tick(&mut self) {
+            self.time += FRAME_MS;
+            self.game.tick(self.time);
+        }
+
+        // presses and releases a key within the next tick
+        fn tap(&mut self, code: KeyCode) {
+            self.game.key_down(code, "", Modifiers::default(), false, self.time + 1.0);
+            self.game.key_up(code, self.time + 2.0);
+            self.tick();
+        }
+    }
+
+    #[test]
+    fn new_game_spawns_asteroids_away_from_the_ship() {
+        let t = TestGame::new();
+        assert!(t.game.game_state == GameState::Running);
+        assert_eq!(t.game.asteroids.len(), t.game.level);
+        for asteroid in t.game.asteroids.iter() {
+            assert!((asteroid.pos - t.game.ship.pos).len() > 300.0);
+        }
+    }
+
+    #[test]
+    fn entities_move_while_running() {
+        let mut t = TestGame::new();
+        let before: Vec<Vec2D> = t.game.asteroids.iter().map(|a| a.pos).collect();
+        t.tick();
+        assert_eq!(t.game.tick, 1);
+        for (asteroid, pos) in t.game.asteroids.iter().zip(before) {
+            assert!((asteroid.pos - pos).len() > 0.0);
+        }
+    }
+
+    #[test]
+    fn shooting_spawns_a_bullet() {
+        let mut t = TestGame::new();
+        t.tap(KeyCode::Space);
+        assert_eq!(t.game.bullets.len(), 1);
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let mut t = TestGame::new();
+        t.tap(KeyCode::KeyP);
+        assert!(t.game.game_state == GameState::Paused);
+        let tick = t.game.tick;
+        t.tick();
+        assert_eq!(t.game.tick, tick);
+        t.tap(KeyCode::KeyP);
+        assert!(t.game.game_state == GameState::Running);
+    }
+
+    #[test]
+    fn hidden_game_pauses_and_stops_ticking() {
+        let mut t = TestGame::new();
+        t.game.set_hidden(true);
+        assert!(t.game.game_state == GameState::Paused);
+        t.tick();
+        assert_eq!(t.game.tick, 0);
+    }
+
+    #[test]
+    fn keys_are_named_after_unmodified_presses() {
+        let mut t = TestGame::new();
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+        t.game.key_down(KeyCode::Digit1, "!", shift, false, 0.0);
+        assert_eq!(t.game.config.key_names.name(KeyCode::Digit1), "1");
+        // e.g. AZERTY, where the key types "1" with shift
+        t.game.key_down(KeyCode::Digit1, "&", Modifiers::default(), false, 0.0);
+        t.game.key_down(KeyCode::Digit1, "1", shift, false, 0.0);
+        assert_eq!(t.game.config.key_names.name(KeyCode::Digit1), "&");
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::default() };
+        t.game.key_down(KeyCode::KeyQ, "a", ctrl, false, 0.0);
+        assert_eq!(t.game.config.key_names.name(KeyCode::KeyQ), "Q");
+    }
+
+    #[test]
+    fn cleared_level_waits_for_the_next_one() {
+        let mut t = TestGame::new();
+        t.game.asteroids.clear();
+        t.tick();
+        assert!(t.game.game_state == GameState::LevelCleared);
+        assert_eq!(t.game.input_context(), InputContext::Menu);
+        t.tap(KeyCode::Enter);
+        assert!(t.game.confirmed);
+        assert_eq!(t.game.asteroids.len(), 0);
+        // the fire key confirms too
+        t.game.confirmed = false;
+        t.tap(KeyCode::Space);
+        assert!(t.game.confirmed);
+
+        let level = t.game.level;
+        t.game.start_next_level();
+        assert!(t.game.game_state == GameState::Running);
+        assert_eq!(t.game.asteroids.len(), level + 1);
+    }
+
+    #[test]
+    fn asteroid_hitting_the_ship_costs_a_life() {
+        let mut t = TestGame::new();
+        let ship_pos = t.game.ship.pos;
+        t.game.asteroids[0].pos = ship_pos;
+        t.tick();
+        assert!(t.game.ship.dead);
+        assert!(t.game.game_state == GameState::Respawning);
+
+        // the pieces of the asteroid would hit the respawned ship
+        for asteroid in t.game.asteroids.iter_mut() {
+            asteroid.pos = Vec2D::zero();
+        }
+        let lives = t.game.lives;
+        t.tap(KeyCode::Enter);
+        assert!(t.game.game_state == GameState::Running);
+        assert!(!t.game.ship.dead);
+        assert_eq!(t.game.lives, lives - 1);
     }
 }
@@ -0,0 +1,171 @@
+use crate::input::{InputIndex, InputContext};
+
+use crate::host::{host};
+
+/*
+ *     Gamepads are polled by the host once per animation frame.
+ * Buttons are sent as a bitmask in the "standard" Gamepad API order,
+ * along with all of the pad's axes and analog button values. The game
+ * uses the analog values in the following slots:
+ */
+pub const AXIS_LEFT_X: usize = 0;
+pub const AXIS_LEFT_Y: usize = 1;
+pub const AXIS_RIGHT_TRIGGER: usize = 2;
+pub const AXIS_LEFT_TRIGGER: usize = 3;
+pub const NUMBER_OF_AXES: usize = 4;
+
+// triggers are buttons 6 and 7 in the "standard" mapping
+const LEFT_TRIGGER_BUTTON: usize = 6;
+const RIGHT_TRIGGER_BUTTON: usize = 7;
+
+/// Picks the analog values the game uses, missing ones are 0.
+pub fn analog_values(axes: &[f64], button_values: &[f64]) -> [f64; NUMBER_OF_AXES] {
+    let mut values = [0.0; NUMBER_OF_AXES];
+    values[AXIS_LEFT_X] = axes.first().cloned().unwrap_or(0.0);
+    values[AXIS_LEFT_Y] = axes.get(1).cloned().unwrap_or(0.0);
+    values[AXIS_RIGHT_TRIGGER] = button_values.get(RIGHT_TRIGGER_BUTTON).cloned().unwrap_or(0.0);
+    values[AXIS_LEFT_TRIGGER] = button_values.get(LEFT_TRIGGER_BUTTON).cloned().unwrap_or(0.0);
+    for value in values.iter_mut() {
+        *value = value.clamp(-1.0, 1.0);
+    }
+    values
+}
+
+pub const DEFAULT_GAMEPAD_BUTTONS: &[(u32, InputIndex)] = &[
+    (0, InputIndex::Shoot),     // A / Cross
+    (5, InputIndex::Shoot),     // Right bumper
+    (12, InputIndex::Forward),  // D-pad up
+    (13, InputIndex::Backward), // D-pad down
+    (14, InputIndex::Left),     // D-pad left
+    (15, InputIndex::Right),    // D-pad right
+    (9, InputIndex::Pause),     // Start
+    (0, InputIndex::Confirm),   // A / Cross
+    (9, InputIndex::Confirm),   // Start
+    (9, InputIndex::Resume),    // Start
+    (1, InputIndex::Resume),    // B / Circle
+];
+
+pub struct GamepadBinds {
+    pub buttons: Vec<(u32, InputIndex)>,
+    pub rotate_axis: usize,
+    pub thrust_axis: usize,
+    pub reverse_axis: usize,
+}
+
+impl GamepadBinds {
+    pub fn new() -> GamepadBinds {
+        GamepadBinds {
+            buttons: DEFAULT_GAMEPAD_BUTTONS.to_vec(),
+            rotate_axis: AXIS_LEFT_X,
+            thrust_axis: AXIS_RIGHT_TRIGGER,
+            reverse_axis: AXIS_LEFT_TRIGGER,
+        }
+    }
+
+    pub fn lookup_button(&self, button: u32, context: InputContext) -> Option<InputIndex> {
+        self.buttons.iter()
+            .find(|&&(bound, input)| bound == button && input.context() == context)
+            .map(|&(_, input)| input)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Rumble {
+    pub strong: f64,
+    pub weak: f64,
+    pub duration: f64,
+}
+
+impl Rumble {
+    pub fn stronger(self, other: Rumble) -> Rumble {
+        Rumble {
+            strong: self.strong.max(other.strong),
+            weak: self.weak.max(other.weak),
+            duration: self.duration.max(other.duration),
+        }
+    }
+
+    pub fn play(&self) {
+        host().gamepad_rumble(self.strong, self.weak, self.duration * 1e3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Config};
+    use crate::input::{Inputs, AxisIndex};
+
+    const NO_AXES: [f64; NUMBER_OF_AXES] = [0.0; NUMBER_OF_AXES];
+
+    #[test]
+    fn analog_values_picks_sticks_and_triggers() {
+        let button_values = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.25, 0.75];
+        let values = analog_values(&[-0.5, 0.5, 0.9, 0.9], &button_values);
+        assert_eq!(values, [-0.5, 0.5, 0.75, 0.25]);
+        // missing values are 0, out of range ones are clamped
+        assert_eq!(analog_values(&[2.0], &[]), [1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn buttons_are_diffed_against_the_previous_poll() {
+        let config = Config::new();
+        let mut inputs = Inputs::new();
+        inputs.gamepad(0, 1 << 0, &NO_AXES, &config);
+        assert!(inputs.just_pressed(InputIndex::Shoot));
+        inputs.tick();
+        // still held, not pressed again
+        inputs.gamepad(0, 1 << 0, &NO_AXES, &config);
+        assert!(!inputs.just_pressed(InputIndex::Shoot));
+        assert!(inputs.is_down(InputIndex::Shoot));
+        inputs.gamepad(0, 0, &NO_AXES, &config);
+        assert!(inputs.just_released(InputIndex::Shoot));
+        assert!(!inputs.is_down(InputIndex::Shoot));
+    }
+
+    #[test]
+    fn pads_are_tracked_separately() {
+        let config = Config::new();
+        let mut inputs = Inputs::new();
+        inputs.gamepad(0, 1 << 12, &NO_AXES, &config);
+        inputs.gamepad(1, 0, &NO_AXES, &config);
+        assert!(inputs.is_down(InputIndex::Forward));
+        inputs.gamepad(1, 1 << 14, &NO_AXES, &config);
+        assert!(inputs.is_down(InputIndex::Forward));
+        assert!(inputs.is_down(InputIndex::Left));
+    }
+
+    #[test]
+    fn buttons_act_in_the_active_context() {
+        let config = Config::new();
+        let mut inputs = Inputs::new();
+        inputs.set_context(InputContext::Paused);
+        inputs.gamepad(0, 1 << 1, &NO_AXES, &config);
+        assert!(inputs.just_pressed(InputIndex::Resume));
+        assert_eq!(config.gamepad_binds.lookup_button(9, InputContext::Gameplay), Some(InputIndex::Pause));
+        assert_eq!(config.gamepad_binds.lookup_button(9, InputContext::Menu), Some(InputIndex::Confirm));
+        assert_eq!(config.gamepad_binds.lookup_button(1, InputContext::Gameplay), None);
+    }
+
+    #[test]
+    fn triggers_thrust_and_reverse() {
+        let config = Config::new();
+        let mut inputs = Inputs::new();
+        let mut axes = NO_AXES;
+        axes[AXIS_RIGHT_TRIGGER] = 1.0;
+        axes[AXIS_LEFT_TRIGGER] = 0.25;
+        axes[AXIS_LEFT_X] = -1.0;
+        inputs.gamepad(0, 0, &axes, &config);
+        let thrust = config.axes[AxisIndex::Thrust as usize].apply(0.75);
+        assert!((inputs.axis(AxisIndex::Thrust) - thrust).abs() < 1e-9);
+        assert_eq!(inputs.axis(AxisIndex::Rotate), -1.0);
+    }
+
+    #[test]
+    fn rumbles_combine_to_the_stronger() {
+        let a = Rumble { strong: 1.0, weak: 0.0, duration: 0.1 };
+        let b = Rumble { strong: 0.5, weak: 0.5, duration: 0.3 };
+        let rumble = a.stronger(b);
+        assert_eq!((rumble.strong, rumble.weak, rumble.duration), (1.0, 0.5, 0.3));
+    }
+}
@@ -1,8 +1,15 @@
-use ::math::Vec2D;
+use crate::math::Vec2D;
+
+/*
+ *     Polygons are slices of vertices in order, either way around, the
+ * last one joined back to the first. They have to be simple, i.e. their
+ * edges don't cross, and `test_polygon_polygon` needs them convex.
+ */
+
 // From Real-Time Collision Detection by Christer Ericson,
 // published by Morgan Kaufmann Publishers, © 2005 Elsevier Inc
 // p.139, ClosestPtPointTriangle
-fn closest_triangle_point(p: Vec2D, a: Vec2D, b: Vec2D, c: Vec2D) -> Vec2D {
+pub fn closest_triangle_point(p: Vec2D, a: Vec2D, b: Vec2D, c: Vec2D) -> Vec2D {
     let ab = b - a;
     let ac = c - a;
     let bc = c - b;
@@ -20,17 +27,17 @@ fn closest_triangle_point(p: Vec2D, a: Vec2D, b: Vec2D, c: Vec2D) -> Vec2D {
     let unom = (p - b).dot(bc);
     let udenom = (p - c).dot(b - c);
     if sdenom <= 0.0 && unom <= 0.0 { return b; } // Vertex region early out
-    if tdenom <= 0.0 && udenom <= 0.0 { return b; } // Vertex region early out
+    if tdenom <= 0.0 && udenom <= 0.0 { return c; } // Vertex region early out
     // P is outside (or on) AB if the triple scalar product [N PA PB] <= 0
     let n = (b - a).cross(c - a);
     let vc = n * (a - p).cross(b - p);
     // If P outside AB and within feature region of AB,
     // return projection of P onto AB
-    if vc < 0.0 && snom >= 0.0 && sdenom >= 0.0 {
+    if vc <= 0.0 && snom >= 0.0 && sdenom >= 0.0 {
         return a + ab.scale(snom / (snom + sdenom));
     }
     // P is outside (or on) BC if the triple scalar product [N PB PC] <= 0
-    let va = n * (b - p).dot(c - p);
+    let va = n * (b - p).cross(c - p);
     // If P outside BC and within feature region of BC,
     // return projection of P onto BC
     if va <= 0.0 && unom >= 0.0 && udenom >= 0.0 {
@@ -50,12 +57,404 @@ fn closest_triangle_point(p: Vec2D, a: Vec2D, b: Vec2D, c: Vec2D) -> Vec2D {
     return a.scale(u) + b.scale(v) + c.scale(w);
 }
 
+// p.129, ClosestPtPointSegment
+pub fn closest_segment_point(p: Vec2D, a: Vec2D, b: Vec2D) -> Vec2D {
+    let ab = b - a;
+    // Project p onto ab, but deferring divide by ab.dot(ab)
+    let t = (p - a).dot(ab);
+    if t <= 0.0 { return a; }
+    let denom = ab.dot(ab);
+    if t >= denom { return b; }
+    return a + ab.scale(t / denom);
+}
+
+/// `p` itself if it's inside the polygon, otherwise the closest point of its edges.
+pub fn closest_polygon_point(p: Vec2D, polygon: &[Vec2D]) -> Vec2D {
+    if point_in_polygon(p, polygon) {
+        return p;
+    }
+    let mut closest = p;
+    let mut closest_dist = f64::INFINITY;
+    for (a, b) in edges(polygon) {
+        let q = closest_segment_point(p, a, b);
+        let diff = p - q;
+        if diff.dot(diff) < closest_dist {
+            closest = q;
+            closest_dist = diff.dot(diff);
+        }
+    }
+    return closest;
+}
+
+fn edges(polygon: &[Vec2D]) -> impl Iterator<Item = (Vec2D, Vec2D)> + '_ {
+    let next = polygon.iter().cycle().skip(1);
+    polygon.iter().cloned().zip(next.cloned())
+}
+
+// p.152, Signed2DTriArea, twice the signed area of abc
+fn signed_triangle_area(a: Vec2D, b: Vec2D, c: Vec2D) -> f64 {
+    return (a.x - c.x) * (b.y - c.y) - (a.y - c.y) * (b.x - c.x);
+}
+
+// p.152, Test2DSegmentSegment
+/// Where the segments ab and cd cross. Segments that only touch,
+/// or that are collinear, don't count.
+pub fn segment_segment(a: Vec2D, b: Vec2D, c: Vec2D, d: Vec2D) -> Option<Vec2D> {
+    // Signs of areas correspond to which side of ab points c and d are
+    let a1 = signed_triangle_area(a, b, d);
+    let a2 = signed_triangle_area(a, b, c);
+    // If c and d are on different sides of ab, areas have different signs
+    if a1 * a2 < 0.0 {
+        // Compute signs for a and b with respect to segment cd
+        let a3 = signed_triangle_area(c, d, a);
+        // Since area is constant a1 - a2 = a3 - a4, or a4 = a3 + a2 - a1
+        let a4 = a3 + a2 - a1;
+        // Points a and b on different sides of cd if areas have different signs
+        if a3 * a4 < 0.0 {
+            let t = a3 / (a3 - a4);
+            return Some(a + (b - a).scale(t));
+        }
+    }
+    return None;
+}
+
+// p.178, IntersectRaySphere
+/// Distance along the ray from `origin` in the unit direction `dir`
+/// to where it enters the circle, 0 if `origin` is inside it.
+pub fn ray_circle(origin: Vec2D, dir: Vec2D, center: Vec2D, radius: f64) -> Option<f64> {
+    let m = origin - center;
+    let b = m.dot(dir);
+    let c = m.dot(m) - radius * radius;
+    // the origin is outside of the circle, and the ray points away from it
+    if c > 0.0 && b > 0.0 { return None; }
+    let discr = b * b - c;
+    if discr < 0.0 { return None; }
+    return Some((-b - discr.sqrt()).max(0.0));
+}
+
+/// Distance along the ray to the segment ab, `None` if they're parallel.
+pub fn ray_segment(origin: Vec2D, dir: Vec2D, a: Vec2D, b: Vec2D) -> Option<f64> {
+    /*
+     *     Solving origin + t*dir = a + s*ab, crossing both sides
+     * with ab gives t, crossing them with dir gives s.
+     */
+    let ab = b - a;
+    let denom = dir.cross(ab);
+    if denom == 0.0 { return None; }
+    let w = a - origin;
+    let t = w.cross(ab) / denom;
+    let s = w.cross(dir) / denom;
+    if t < 0.0 || !(0.0..=1.0).contains(&s) { return None; }
+    return Some(t);
+}
+
+/// Like `ray_circle`, 0 if `origin` is inside the polygon.
+pub fn ray_polygon(origin: Vec2D, dir: Vec2D, polygon: &[Vec2D]) -> Option<f64> {
+    if point_in_polygon(origin, polygon) {
+        return Some(0.0);
+    }
+    edges(polygon)
+        .filter_map(|(a, b)| ray_segment(origin, dir, a, b))
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+// Crossing test, counting the edges a ray going right from p crosses
+pub fn point_in_polygon(p: Vec2D, polygon: &[Vec2D]) -> bool {
+    let mut inside = false;
+    for (a, b) in edges(polygon) {
+        if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (b.x - a.x) * (p.y - a.y) / (b.y - a.y) {
+            inside = !inside;
+        }
+    }
+    return inside;
+}
+
+/// Positive if the vertices go from the x axis towards the y axis, which is
+/// clockwise on screen, where y points down.
+pub fn polygon_area(polygon: &[Vec2D]) -> f64 {
+    edges(polygon).map(|(a, b)| a.cross(b)).sum::<f64>() / 2.0
+}
+
+/// The center of mass, or the average vertex if the polygon has no area.
+pub fn polygon_centroid(polygon: &[Vec2D]) -> Vec2D {
+    let area = polygon_area(polygon);
+    if area == 0.0 {
+        let sum = polygon.iter().fold(Vec2D::zero(), |sum, &v| sum + v);
+        return sum.scale(1.0 / polygon.len() as f64);
+    }
+    let sum = edges(polygon).fold(Vec2D::zero(), |sum, (a, b)| sum + (a + b).scale(a.cross(b)));
+    return sum.scale(1.0 / (6.0 * area));
+}
+
+fn project(polygon: &[Vec2D], axis: Vec2D) -> (f64, f64) {
+    polygon.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        let d = v.dot(axis);
+        (min.min(d), max.max(d))
+    })
+}
+
+/// Separating axis test for convex polygons, touching ones intersect.
+pub fn test_polygon_polygon(p: &[Vec2D], q: &[Vec2D]) -> bool {
+    // for polygons, the only candidate axes are the edge normals
+    for (a, b) in edges(p).chain(edges(q)) {
+        let axis = Vec2D { x: a.y - b.y, y: b.x - a.x };
+        let (pmin, pmax) = project(p, axis);
+        let (qmin, qmax) = project(q, axis);
+        if pmax < qmin || qmax < pmin {
+            return false;
+        }
+    }
+    return true;
+}
+
 pub fn test_circle_triangle(center: Vec2D, radius: f64, a: Vec2D, b: Vec2D, c: Vec2D) -> bool {
     let diff = center - closest_triangle_point(center, a, b, c);
     return diff.dot(diff) <= radius * radius;
 }
 
+pub fn test_circle_polygon(center: Vec2D, radius: f64, polygon: &[Vec2D]) -> bool {
+    let diff = center - closest_polygon_point(center, polygon);
+    return diff.dot(diff) <= radius * radius;
+}
+
 pub fn test_circle_point(center: Vec2D, radius: f64, point: Vec2D) -> bool {
     let diff = center - point;
     return diff.dot(diff) <= radius * radius
 }
+
+/*
+ *     Property tests: every query is checked on random shapes against
+ * a brute force answer, from sampling edges, marching along rays or
+ * counting grid cells, within the precision of the sampling.
+ */
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+    use crate::rng::{GameRng, Rng, seeded_rng};
+
+    fn random_point(rng: &mut GameRng, size: f64) -> Vec2D {
+        Vec2D { x: rng.gen_range(-size, size), y: rng.gen_range(-size, size) }
+    }
+
+    fn distance(a: Vec2D, b: Vec2D) -> f64 {
+        (a - b).len()
+    }
+
+    // vertices around `center`, less than half a turn apart, so the polygon is simple
+    fn star_polygon(rng: &mut GameRng, center: Vec2D, convex: bool) -> Vec<Vec2D> {
+        let n = rng.gen_range(3, 9);
+        let slot = 2.0 * PI / n as f64;
+        let angles: Vec<f64> = (0..n).map(|i| (i as f64 + rng.gen_range(0.0, 0.4)) * slot).collect();
+        let radius = rng.gen_range(1.0, 4.0);
+        let stretch = rng.gen_range(0.5, 1.5);
+        angles.iter().map(|&angle| {
+            // on an ellipse, the polygon is convex
+            let r = if convex { radius } else { rng.gen_range(0.5, 4.0) };
+            center + Vec2D { x: r * angle.cos() * stretch, y: r * angle.sin() }
+        }).collect()
+    }
+
+    // the winding number, from adding up the angles the edges turn around p
+    fn brute_inside(p: Vec2D, polygon: &[Vec2D]) -> bool {
+        let winding: f64 = edges(polygon).map(|(a, b)| {
+            let (u, v) = (a - p, b - p);
+            u.cross(v).atan2(u.dot(v))
+        }).sum();
+        winding.abs() > PI
+    }
+
+    fn sample_segment(a: Vec2D, b: Vec2D, samples: usize) -> impl Iterator<Item = Vec2D> {
+        (0..=samples).map(move |i| a + (b - a).scale(i as f64 / samples as f64))
+    }
+
+    fn brute_boundary_distance(p: Vec2D, polygon: &[Vec2D]) -> f64 {
+        edges(polygon)
+            .flat_map(|(a, b)| sample_segment(a, b, 2000))
+            .map(|q| distance(p, q))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn brute_distance(p: Vec2D, polygon: &[Vec2D]) -> f64 {
+        if brute_inside(p, polygon) { 0.0 } else { brute_boundary_distance(p, polygon) }
+    }
+
+    #[test]
+    fn closest_triangle_point_third_vertex() {
+        let (a, b, c) = (Vec2D { x: 0.0, y: 0.0 }, Vec2D { x: 4.0, y: 0.0 }, Vec2D { x: 0.0, y: 4.0 });
+        let q = closest_triangle_point(Vec2D { x: -1.0, y: 6.0 }, a, b, c);
+        assert_eq!((q.x, q.y), (c.x, c.y));
+    }
+
+    #[test]
+    fn closest_triangle_point_matches_brute_force() {
+        let mut rng = seeded_rng(1);
+        for _ in 0..300 {
+            let (a, b, c) = (random_point(&mut rng, 5.0), random_point(&mut rng, 5.0), random_point(&mut rng, 5.0));
+            let p = random_point(&mut rng, 10.0);
+            let q = closest_triangle_point(p, a, b, c);
+            let triangle = [a, b, c];
+            assert!(brute_distance(q, &triangle) < 1e-2, "{:?} is outside of {:?}", q, triangle);
+            assert!((distance(p, q) - brute_distance(p, &triangle)).abs() < 1e-2,
+                    "{:?} isn't the closest to {:?} in {:?}", q, p, triangle);
+        }
+    }
+
+    #[test]
+    fn closest_polygon_point_matches_brute_force() {
+        let mut rng = seeded_rng(2);
+        for _ in 0..300 {
+            let center = random_point(&mut rng, 2.0);
+            let polygon = star_polygon(&mut rng, center, false);
+            let p = random_point(&mut rng, 8.0);
+            let q = closest_polygon_point(p, &polygon);
+            assert!((distance(p, q) - brute_distance(p, &polygon)).abs() < 1e-2);
+            assert!(test_circle_polygon(p, distance(p, q) + 1e-9, &polygon));
+            let r = closest_segment_point(p, polygon[0], polygon[1]);
+            let brute = sample_segment(polygon[0], polygon[1], 2000).map(|s| distance(p, s)).fold(f64::INFINITY, f64::min);
+            assert!((distance(p, r) - brute).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn point_in_polygon_matches_winding_number() {
+        let mut rng = seeded_rng(3);
+        let mut inside = 0;
+        for _ in 0..200 {
+            let polygon = star_polygon(&mut rng, Vec2D::zero(), false);
+            for _ in 0..20 {
+                let p = random_point(&mut rng, 5.0);
+                assert_eq!(point_in_polygon(p, &polygon), brute_inside(p, &polygon), "{:?} in {:?}", p, polygon);
+                if brute_inside(p, &polygon) { inside += 1; }
+            }
+        }
+        assert!(inside > 100);
+    }
+
+    #[test]
+    fn segment_segment_matches_brute_force() {
+        let mut rng = seeded_rng(4);
+        let mut crossing = 0;
+        for _ in 0..300 {
+            let (a, b, c, d) = (random_point(&mut rng, 5.0), random_point(&mut rng, 5.0),
+                                random_point(&mut rng, 5.0), random_point(&mut rng, 5.0));
+            // the closest samples on both segments
+            let brute = sample_segment(a, b, 200)
+                .flat_map(|p| sample_segment(c, d, 200).map(move |q| distance(p, q)))
+                .fold(f64::INFINITY, f64::min);
+            match segment_segment(a, b, c, d) {
+                Some(p) => {
+                    crossing += 1;
+                    assert!(brute < 0.1);
+                    assert!(distance(p, closest_segment_point(p, a, b)) < 1e-9);
+                    assert!(distance(p, closest_segment_point(p, c, d)) < 1e-9);
+                },
+                None => assert!(brute > 1e-3, "missed {:?} {:?} crossing {:?} {:?}", a, b, c, d),
+            }
+        }
+        assert!(crossing > 30);
+    }
+
+    // the first step along the ray that's inside
+    fn march(origin: Vec2D, dir: Vec2D, inside: impl Fn(Vec2D) -> bool) -> Option<f64> {
+        (0..3000).map(|i| i as f64 * 0.01).find(|&t| inside(origin + dir.scale(t)))
+    }
+
+    #[test]
+    fn rays_match_marching() {
+        let mut rng = seeded_rng(5);
+        let mut hits = 0;
+        for _ in 0..300 {
+            let origin = random_point(&mut rng, 10.0);
+            let dir = Vec2D { x: 1.0, y: 0.0 }.rotate(rng.gen_range(0.0, 2.0 * PI));
+            let center = random_point(&mut rng, 3.0);
+            let polygon = star_polygon(&mut rng, center, false);
+            let center = random_point(&mut rng, 5.0);
+            let radius = rng.gen_range(0.5, 3.0);
+
+            let circle = ray_circle(origin, dir, center, radius);
+            match march(origin, dir, |p| distance(p, center) <= radius) {
+                Some(t) => {
+                    hits += 1;
+                    assert!(circle.is_some_and(|hit| hit <= t && t - hit < 0.02));
+                },
+                // grazing the circle, between the steps
+                None => assert!(circle.is_none_or(|hit| (distance(origin + dir.scale(hit), center) - radius).abs() < 1e-6)),
+            }
+
+            let hit = ray_polygon(origin, dir, &polygon);
+            match march(origin, dir, |p| brute_inside(p, &polygon)) {
+                Some(t) => assert!(hit.is_some_and(|hit| hit <= t && t - hit < 0.02)),
+                None => assert!(hit.is_none_or(|hit| brute_boundary_distance(origin + dir.scale(hit), &polygon) < 1e-2)),
+            }
+        }
+        assert!(hits > 20);
+    }
+
+    #[test]
+    fn area_is_positive_from_x_towards_y() {
+        let square = [Vec2D { x: 0.0, y: 0.0 }, Vec2D { x: 2.0, y: 0.0 }, Vec2D { x: 2.0, y: 2.0 }, Vec2D { x: 0.0, y: 2.0 }];
+        assert_eq!(polygon_area(&square), 4.0);
+        let centroid = polygon_centroid(&square);
+        assert_eq!((centroid.x, centroid.y), (1.0, 1.0));
+    }
+
+    #[test]
+    fn area_and_centroid_match_grid_counting() {
+        let mut rng = seeded_rng(6);
+        let step = 0.03;
+        for _ in 0..10 {
+            let center = random_point(&mut rng, 2.0);
+            let polygon = star_polygon(&mut rng, center, false);
+            let (mut count, mut sum) = (0, Vec2D::zero());
+            // the polygon is within 6 of its center
+            for i in -200..200 {
+                for j in -200..200 {
+                    let p = center + Vec2D { x: (i as f64 + 0.5) * step, y: (j as f64 + 0.5) * step };
+                    if point_in_polygon(p, &polygon) {
+                        count += 1;
+                        sum += p;
+                    }
+                }
+            }
+            let area = polygon_area(&polygon);
+            let perimeter: f64 = edges(&polygon).map(|(a, b)| distance(a, b)).sum();
+            // going around the center, the vertices go from x towards y
+            assert!(area > 0.0);
+            assert!((area - count as f64 * step * step).abs() < perimeter * step);
+            assert!(distance(polygon_centroid(&polygon), sum.scale(1.0 / count as f64)) < 0.05);
+
+            let reversed: Vec<Vec2D> = polygon.iter().rev().cloned().collect();
+            assert!((polygon_area(&reversed) + area).abs() < 1e-9);
+            assert!(distance(polygon_centroid(&reversed), polygon_centroid(&polygon)) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn separating_axes_match_brute_force() {
+        let mut rng = seeded_rng(7);
+        let mut overlapping = 0;
+        for _ in 0..100 {
+            let center = random_point(&mut rng, 4.0);
+            let p = star_polygon(&mut rng, center, true);
+            let center = random_point(&mut rng, 4.0);
+            let q = star_polygon(&mut rng, center, true);
+            // a grid point in both
+            let common = (-85..85).flat_map(|i| (-85..85).map(move |j| Vec2D { x: i as f64 * 0.1, y: j as f64 * 0.1 }))
+                .any(|v| point_in_polygon(v, &p) && point_in_polygon(v, &q));
+            let test = test_polygon_polygon(&p, &q);
+            if common {
+                overlapping += 1;
+                assert!(test);
+            } else if test {
+                // then the overlap is thinner than the grid
+                let close = edges(&p)
+                    .flat_map(|(a, b)| sample_segment(a, b, 500))
+                    .any(|v| distance(v, closest_polygon_point(v, &q)) < 0.2);
+                assert!(close, "{:?} and {:?} are apart", p, q);
+            }
+            assert_eq!(test, test_polygon_polygon(&q, &p));
+        }
+        assert!(overlapping > 20);
+    }
+}
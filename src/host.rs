@@ -0,0 +1,67 @@
+use std::cell::{RefCell};
+use std::rc::{Rc};
+use crate::rng::{RNGSourceError};
+
+/*
+ *     Everything the game needs from the outside world goes through
+ * the `Host`. In the browser it's `WasmHost`, calling functions imported
+ * from demo.js, natively it's `NativeHost`, which keeps the page state
+ * in memory, so the game can be built and tested without a browser.
+ * WASI builds aren't in a browser either, they use `NativeHost` too, which
+ * is how the tests run on wasm.
+ */
+pub trait Host {
+    /// Milliseconds since an arbitrary point, the clock of event timestamps.
+    fn now(&self) -> f64;
+    fn fill_random(&self, buf: &mut [u8]) -> Result<(), RNGSourceError>;
+    fn puts(&self, s: &str);
+    fn alert(&self, n: f64);
+
+    // the SVG drawing, see `update_svg`, `update_viewport` and `apply_theme`,
+    // `layer` is a `Style`
+    fn svg_set_path(&self, layer: u32, path: &str);
+    fn svg_set_style(&self, layer: u32, stroke: &str, width: f64, opacity: f64);
+    fn svg_set_background(&self, color: &str);
+    fn svg_set_transform(&self, transform: &str);
+    fn svg_set_clip(&self, x: f64, y: f64, width: f64, height: f64);
+    // a frame as a `DisplayList`, empty to clear the drawing
+    fn draw_display_list(&self, list: &[u8]);
+
+    fn storage_get(&self, key: &str) -> Option<String>;
+    fn storage_set(&self, key: &str, value: &str);
+
+    fn gamepad_rumble(&self, strong: f64, weak: f64, duration_ms: f64);
+
+    // event loops, see eventloop.rs
+    fn event_loop_new(&self) -> u32;
+    fn event_loop_raf(&self, id: u32);
+    fn event_loop_shutdown(&self, id: u32) -> bool;
+    fn event_loop_set_timer(&self, id: u32, delay_ms: f64, repeat: bool) -> u32;
+    fn event_loop_clear_timer(&self, id: u32, handle: u32) -> bool;
+    fn event_loop_subscribe(&self, id: u32, categories: u32);
+    fn event_loop_unsubscribe(&self, id: u32, categories: u32);
+    fn event_loop_set_target(&self, id: u32, selector: &str) -> bool;
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn default_host() -> Rc<dyn Host> {
+    Rc::new(crate::wasm_host::WasmHost)
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn default_host() -> Rc<dyn Host> {
+    crate::native_host::default_host()
+}
+
+thread_local! {
+    static HOST: RefCell<Rc<dyn Host>> = RefCell::new(default_host());
+}
+
+pub fn host() -> Rc<dyn Host> {
+    HOST.with(|host| host.borrow().clone())
+}
+
+/// Replaces the host, e.g. with a `NativeHost` whose state a test inspects.
+pub fn set_host(new_host: Rc<dyn Host>) {
+    HOST.with(|host| *host.borrow_mut() = new_host);
+}
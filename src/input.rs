@@ -1,4 +1,7 @@
-use ::game::{Config};
+use crate::game::{Config};
+use crate::gamepad::{NUMBER_OF_AXES};
+use crate::math::{Vec2D};
+use crate::keycode::{KeyCode};
 
 /*
  *     The reason we don't use a simple bool for key state is that
@@ -43,24 +46,222 @@ impl KeyState {
     }
 }
 
-#[derive(Copy, Clone)]
+/*
+ *     What keys do depends on what's on the screen. Every action belongs
+ * to exactly one context, and only actions of the active context can be
+ * triggered, so the same key may be bound to an action in every context.
+ */
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InputContext {
+    Gameplay = 0,
+    Menu = 1,
+    TextEntry = 2,
+    Paused = 3,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum InputIndex {
     Shoot = 0,
     Forward = 1,
     Backward = 2,
     Left = 3,
     Right = 4,
-    _NumberOfInputs = 5,
+    Pause = 5,
+    Confirm = 6,
+    Resume = 7,
+    Submit = 8,
+    Cancel = 9,
+    Erase = 10,
+    _NumberOfInputs = 11,
+}
+
+pub const ALL_INPUTS: &[InputIndex] = &[
+    InputIndex::Shoot,
+    InputIndex::Forward,
+    InputIndex::Backward,
+    InputIndex::Left,
+    InputIndex::Right,
+    InputIndex::Pause,
+    InputIndex::Confirm,
+    InputIndex::Resume,
+    InputIndex::Submit,
+    InputIndex::Cancel,
+    InputIndex::Erase,
+];
+
+impl InputIndex {
+    pub fn from_u32(idx: u32) -> Option<InputIndex> {
+        ALL_INPUTS.get(idx as usize).cloned()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            InputIndex::Shoot => "shoot",
+            InputIndex::Forward => "forward",
+            InputIndex::Backward => "backward",
+            InputIndex::Left => "left",
+            InputIndex::Right => "right",
+            InputIndex::Pause => "pause",
+            InputIndex::Confirm => "confirm",
+            InputIndex::Resume => "resume",
+            InputIndex::Submit => "submit",
+            InputIndex::Cancel => "cancel",
+            InputIndex::Erase => "erase",
+            InputIndex::_NumberOfInputs => "",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<InputIndex> {
+        ALL_INPUTS.iter().find(|input| input.name() == name).cloned()
+    }
+
+    pub fn context(&self) -> InputContext {
+        match *self {
+            InputIndex::Shoot |
+            InputIndex::Forward |
+            InputIndex::Backward |
+            InputIndex::Left |
+            InputIndex::Right |
+            InputIndex::Pause |
+            InputIndex::_NumberOfInputs => InputContext::Gameplay,
+            InputIndex::Confirm => InputContext::Menu,
+            InputIndex::Resume => InputContext::Paused,
+            InputIndex::Submit |
+            InputIndex::Cancel |
+            InputIndex::Erase => InputContext::TextEntry,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ControlScheme {
+    Keyboard = 0,
+    // the ship turns towards the mouse cursor, mouse buttons shoot and thrust
+    MouseAim = 1,
+}
+
+impl ControlScheme {
+    pub fn from_u32(scheme: u32) -> Option<ControlScheme> {
+        match scheme {
+            0 => Some(ControlScheme::Keyboard),
+            1 => Some(ControlScheme::MouseAim),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AxisIndex {
+    Rotate = 0,
+    Thrust = 1,
+    _NumberOfAxes = 2,
+}
+
+impl AxisIndex {
+    pub fn from_u32(idx: u32) -> Option<AxisIndex> {
+        match idx {
+            0 => Some(AxisIndex::Rotate),
+            1 => Some(AxisIndex::Thrust),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ResponseCurve {
+    Linear = 0,
+    // finer control near the center, at the cost of coarser control near the edge
+    Quadratic = 1,
+    Cubic = 2,
+}
+
+impl ResponseCurve {
+    pub fn from_u32(curve: u32) -> Option<ResponseCurve> {
+        match curve {
+            0 => Some(ResponseCurve::Linear),
+            1 => Some(ResponseCurve::Quadratic),
+            2 => Some(ResponseCurve::Cubic),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AxisConfig {
+    pub dead_zone: f64,
+    pub curve: ResponseCurve,
+}
+
+impl AxisConfig {
+    /// Applies the dead zone and the response curve to a raw value in range -1..1.
+    pub fn apply(&self, value: f64) -> f64 {
+        let magnitude = value.abs().min(1.0);
+        if magnitude <= self.dead_zone {
+            return 0.0;
+        }
+        let scaled = (magnitude - self.dead_zone) / (1.0 - self.dead_zone);
+        let curved = match self.curve {
+            ResponseCurve::Linear => scaled,
+            ResponseCurve::Quadratic => scaled * scaled,
+            ResponseCurve::Cubic => scaled * scaled * scaled,
+        };
+        value.signum() * curved
+    }
+}
+
+// Devices that report analog values, each one is tracked separately
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AxisSource {
+    Gamepad(u32),
+    Touch,
+    // set by the page, e.g. for assistive devices
+    Host,
 }
 
+struct AnalogInput {
+    source: AxisSource,
+    values: [f64; AxisIndex::_NumberOfAxes as usize],
+}
+
+/*
+ *     Inputs remember when within a tick they went up or down, so that
+ * a tap shorter than a tick only thrusts for as long as it was held.
+ * Times are host timestamps in milliseconds. Changes from devices without
+ * timestamps happen at the tick boundaries, i.e. a press counts from the
+ * start of the tick and a release counts until its end.
+ */
 pub struct Inputs {
+    context: InputContext,
     inputs: [KeyState; InputIndex::_NumberOfInputs as usize],
+    // state changes since the last tick: input, time, went down
+    changes: Vec<(InputIndex, f64, bool)>,
+    down_at_tick_start: [bool; InputIndex::_NumberOfInputs as usize],
+    tick_time: Option<f64>,
+    // fraction of the current tick each input was held for
+    held: [f64; InputIndex::_NumberOfInputs as usize],
+    // edges since the last tick, a tap within a tick sets both
+    pressed: [bool; InputIndex::_NumberOfInputs as usize],
+    released: [bool; InputIndex::_NumberOfInputs as usize],
+    analog: Vec<AnalogInput>,
+    // gamepad index and buttons from the previous poll
+    gamepad_buttons: Vec<(u32, u32)>,
+    aim: Option<Vec2D>,
 }
 
 impl Inputs {
     pub fn new() -> Inputs {
         Inputs {
+            context: InputContext::Gameplay,
             inputs: [KeyState::Up; InputIndex::_NumberOfInputs as usize],
+            changes: Vec::new(),
+            down_at_tick_start: [false; InputIndex::_NumberOfInputs as usize],
+            tick_time: None,
+            held: [0.0; InputIndex::_NumberOfInputs as usize],
+            pressed: [false; InputIndex::_NumberOfInputs as usize],
+            released: [false; InputIndex::_NumberOfInputs as usize],
+            analog: Vec::new(),
+            gamepad_buttons: Vec::new(),
+            aim: None,
         }
     }
 
@@ -72,21 +273,494 @@ impl Inputs {
         self.inputs[idx as usize].is_down()
     }
 
+    /// True if the input went down since the last tick.
+    pub fn just_pressed(&self, idx: InputIndex) -> bool {
+        self.pressed[idx as usize]
+    }
+
+    /// True if the input went up since the last tick.
+    pub fn just_released(&self, idx: InputIndex) -> bool {
+        self.released[idx as usize]
+    }
+
+    pub fn context(&self) -> InputContext {
+        self.context
+    }
+
+    /// Switches to another context, releasing everything held in the old one.
+    /// Keys still held have to be pressed again to act in the new context.
+    pub fn set_context(&mut self, context: InputContext) {
+        if context != self.context {
+            self.release_all();
+            self.context = context;
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        for input in self.inputs.iter_mut() {
+            *input = KeyState::Up;
+        }
+        self.changes.clear();
+        self.down_at_tick_start = [false; InputIndex::_NumberOfInputs as usize];
+        self.pressed = [false; InputIndex::_NumberOfInputs as usize];
+        self.released = [false; InputIndex::_NumberOfInputs as usize];
+    }
+
+    /// Fraction of the current tick the input was held for, in range 0..1.
+    pub fn held(&self, idx: InputIndex) -> f64 {
+        self.held[idx as usize]
+    }
+
+    fn digital_axis(&self, negative: InputIndex, positive: InputIndex) -> Option<f64> {
+        let (negative, positive) = (self.held(negative), self.held(positive));
+        if negative == 0.0 && positive == 0.0 {
+            return None;
+        }
+        Some(positive - negative)
+    }
+
+    fn analog_axis(&self, idx: AxisIndex) -> f64 {
+        self.analog.iter()
+            .map(|input| input.values[idx as usize])
+            .fold(0.0, |acc, v| if v.abs() > acc.abs() { v } else { acc })
+    }
+
+    /// Axis value in range -1..1.
+    /// Keys give full deflection and take precedence over analog devices,
+    /// otherwise the device with the largest deflection wins.
+    pub fn axis(&self, idx: AxisIndex) -> f64 {
+        let digital = match idx {
+            AxisIndex::Rotate => self.digital_axis(InputIndex::Left, InputIndex::Right),
+            AxisIndex::Thrust => self.digital_axis(InputIndex::Backward, InputIndex::Forward),
+            AxisIndex::_NumberOfAxes => None,
+        };
+        digital.unwrap_or_else(|| self.analog_axis(idx))
+    }
+
+    /// Sets a raw analog value from `source`, shaped by the axis config.
+    pub fn set_axis(&mut self, source: AxisSource, idx: AxisIndex, value: f64, config: &Config) {
+        let value = config.axes[idx as usize].apply(value);
+        let pos = match self.analog.iter().position(|input| input.source == source) {
+            Some(pos) => pos,
+            None => {
+                self.analog.push(AnalogInput {
+                    source: source,
+                    values: [0.0; AxisIndex::_NumberOfAxes as usize],
+                });
+                self.analog.len() - 1
+            },
+        };
+        self.analog[pos].values[idx as usize] = value;
+    }
+
+    /// The point in field coordinates the ship should turn towards, if any.
+    pub fn aim(&self) -> Option<Vec2D> {
+        self.aim
+    }
+
+    /// Works out how long inputs were held since the last tick, up to `time`,
+    /// as a fraction of a tick of `config.delta_t`, so that a short tap
+    /// thrusts the same whatever the frame rate. Inputs held since the last
+    /// tick count as a full tick. Has to be called before the tick reads axes.
+    pub fn begin_tick(&mut self, time: f64, config: &Config) {
+        let start = self.tick_time.unwrap_or(time);
+        let len = time - start;
+        let tick_ms = config.delta_t * 1e3;
+        for &input in ALL_INPUTS.iter() {
+            let idx = input as usize;
+            let mut down = self.down_at_tick_start[idx];
+            let mut down_from = start;
+            let mut held = 0.0;
+            for &(changed, change_time, went_down) in self.changes.iter() {
+                if changed != input {
+                    continue;
+                }
+                let change_time = change_time.max(start).min(time);
+                if went_down && !down {
+                    down_from = change_time;
+                } else if !went_down && down {
+                    held += change_time - down_from;
+                }
+                down = went_down;
+            }
+            if down {
+                held += time - down_from;
+            }
+            // held throughout counts fully, whatever the rounding of `len`
+            self.held[idx] = if len > 0.0 && held < len {
+                (held / tick_ms).min(1.0)
+            } else if len > 0.0 || down {
+                1.0
+            } else {
+                0.0
+            };
+            self.down_at_tick_start[idx] = down;
+        }
+        self.changes.clear();
+        self.tick_time = Some(time);
+    }
+
     pub fn tick(&mut self) {
         for input in self.inputs.iter_mut() {
             input.tick();
         }
+        self.pressed = [false; InputIndex::_NumberOfInputs as usize];
+        self.released = [false; InputIndex::_NumberOfInputs as usize];
+    }
+
+    /// Presses the input at the start of the tick.
+    pub fn action_down(&mut self, idx: InputIndex) {
+        self.action_down_at(idx, f64::NEG_INFINITY);
+    }
+
+    /// Releases the input at the end of the tick.
+    pub fn action_up(&mut self, idx: InputIndex) {
+        self.action_up_at(idx, f64::INFINITY);
+    }
+
+    pub fn action_down_at(&mut self, idx: InputIndex, time: f64) {
+        if idx.context() != self.context {
+            return;
+        }
+        if !self.is_down(idx) {
+            self.pressed[idx as usize] = true;
+            self.record_change(idx, time, true);
+        }
+        self.inputs[idx as usize].down();
+    }
+
+    pub fn action_up_at(&mut self, idx: InputIndex, time: f64) {
+        if self.is_down(idx) {
+            self.released[idx as usize] = true;
+            self.record_change(idx, time, false);
+        }
+        self.inputs[idx as usize].up();
+    }
+
+    fn record_change(&mut self, idx: InputIndex, time: f64, went_down: bool) {
+        /* Before the first tick there's no previous tick to measure from, so
+         * the first timed input starts the interval. Otherwise the first tick
+         * would be empty and a tap before it wouldn't thrust at all. */
+        if self.tick_time.is_none() && time.is_finite() {
+            self.tick_time = Some(time);
+        }
+        self.changes.push((idx, time, went_down));
+    }
+
+    /// Returns false if the key isn't bound in the active context.
+    pub fn key_down(&mut self, code: KeyCode, time: f64, config: &Config) -> bool {
+        match config.lookup_input_key(code, self.context) {
+            Some(index) => {
+                self.action_down_at(index, time);
+                true
+            },
+            None => false,
+        }
     }
 
-    pub fn key_down(&mut self, code: u32, config: &Config) {
-        if let Some(index) = config.lookup_input_key(code) {
-            self.inputs[index as usize].down();
+    pub fn key_up(&mut self, code: KeyCode, time: f64, config: &Config) {
+        if let Some(index) = config.lookup_input_key(code, self.context) {
+            self.action_up_at(index, time);
         }
     }
 
-    pub fn key_up(&mut self, code: u32, config: &Config) {
-        if let Some(index) = config.lookup_input_key(code) {
-            self.inputs[index as usize].up();
+    pub fn mouse_move(&mut self, pos: Vec2D, config: &Config) {
+        if config.control_scheme == ControlScheme::MouseAim {
+            self.aim = Some(pos);
         }
     }
+
+    pub fn mouse_down(&mut self, button: u32, time: f64, config: &Config) {
+        if config.control_scheme != ControlScheme::MouseAim {
+            return;
+        }
+        if let Some(index) = config.lookup_mouse_button(button, self.context) {
+            self.action_down_at(index, time);
+        }
+    }
+
+    pub fn mouse_up(&mut self, button: u32, time: f64, config: &Config) {
+        if let Some(index) = config.lookup_mouse_button(button, self.context) {
+            self.action_up_at(index, time);
+        }
+    }
+
+    /// Stops aiming and releases mouse buttons, e.g. when switching control schemes.
+    pub fn release_mouse(&mut self, config: &Config) {
+        self.aim = None;
+        for &(_, input) in config.mouse_binds.iter() {
+            self.action_up(input);
+        }
+    }
+
+    /// Updates the state of a gamepad from a poll by the host.
+    /// Button presses are found by comparing against the previous poll.
+    pub fn gamepad(&mut self, index: u32, buttons: u32, axes: &[f64; NUMBER_OF_AXES], config: &Config) {
+        let pos = match self.gamepad_buttons.iter().position(|&(pad, _)| pad == index) {
+            Some(pos) => pos,
+            None => {
+                self.gamepad_buttons.push((index, 0));
+                self.gamepad_buttons.len() - 1
+            },
+        };
+        let binds = &config.gamepad_binds;
+
+        let changed = self.gamepad_buttons[pos].1 ^ buttons;
+        for button in 0..32 {
+            if changed & (1 << button) == 0 {
+                continue;
+            }
+            if let Some(input) = binds.lookup_button(button, self.context) {
+                if buttons & (1 << button) != 0 {
+                    self.action_down(input);
+                } else {
+                    self.action_up(input);
+                }
+            }
+        }
+
+        self.gamepad_buttons[pos].1 = buttons;
+        let source = AxisSource::Gamepad(index);
+        let rotate = axes[binds.rotate_axis];
+        let thrust = axes[binds.thrust_axis] - axes[binds.reverse_axis];
+        self.set_axis(source, AxisIndex::Rotate, rotate, config);
+        self.set_axis(source, AxisIndex::Thrust, thrust, config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ticks of 20ms, for round numbers
+    fn config() -> Config {
+        let mut config = Config::new();
+        config.delta_t = 0.02;
+        config
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "got {}, expected {}", actual, expected);
+    }
+
+    fn assert_held(inputs: &Inputs, expected: f64) {
+        assert_close(inputs.held(InputIndex::Forward), expected);
+    }
+
+    #[test]
+    fn dead_zone_and_curves() {
+        let linear = AxisConfig { dead_zone: 0.2, curve: ResponseCurve::Linear };
+        assert_eq!(linear.apply(0.2), 0.0);
+        assert_eq!(linear.apply(-0.1), 0.0);
+        assert_close(linear.apply(0.6), 0.5);
+        assert_close(linear.apply(-0.6), -0.5);
+        // out of range values are clamped
+        assert_close(linear.apply(1.5), 1.0);
+        assert_close(linear.apply(-3.0), -1.0);
+
+        let quadratic = AxisConfig { dead_zone: 0.2, curve: ResponseCurve::Quadratic };
+        assert_close(quadratic.apply(0.6), 0.25);
+        assert_close(quadratic.apply(-0.6), -0.25);
+        let cubic = AxisConfig { dead_zone: 0.0, curve: ResponseCurve::Cubic };
+        assert_close(cubic.apply(0.5), 0.125);
+        assert_close(cubic.apply(-1.0), -1.0);
+    }
+
+    #[test]
+    fn set_axis_applies_the_axis_config() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        let dead_zone = config.axes[AxisIndex::Thrust as usize].dead_zone;
+        inputs.set_axis(AxisSource::Host, AxisIndex::Thrust, dead_zone / 2.0, &config);
+        assert_eq!(inputs.axis(AxisIndex::Thrust), 0.0);
+        inputs.set_axis(AxisSource::Host, AxisIndex::Thrust, 1.0, &config);
+        assert_close(inputs.axis(AxisIndex::Thrust), 1.0);
+        assert_eq!(inputs.axis(AxisIndex::Rotate), 0.0);
+    }
+
+    #[test]
+    fn largest_analog_deflection_wins() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.set_axis(AxisSource::Gamepad(0), AxisIndex::Rotate, 0.5, &config);
+        inputs.set_axis(AxisSource::Touch, AxisIndex::Rotate, -0.8, &config);
+        let touch = config.axes[AxisIndex::Rotate as usize].apply(-0.8);
+        assert_close(inputs.axis(AxisIndex::Rotate), touch);
+        // a source replaces its own value
+        inputs.set_axis(AxisSource::Touch, AxisIndex::Rotate, 0.0, &config);
+        let gamepad = config.axes[AxisIndex::Rotate as usize].apply(0.5);
+        assert_close(inputs.axis(AxisIndex::Rotate), gamepad);
+    }
+
+    #[test]
+    fn keys_take_precedence_over_analog() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.set_axis(AxisSource::Touch, AxisIndex::Rotate, 1.0, &config);
+        inputs.begin_tick(0.0, &config);
+        inputs.action_down_at(InputIndex::Left, 10.0);
+        inputs.begin_tick(20.0, &config);
+        assert_close(inputs.axis(AxisIndex::Rotate), -0.5);
+        // opposite keys cancel out, still overriding the stick
+        inputs.action_down_at(InputIndex::Right, 20.0);
+        inputs.begin_tick(40.0, &config);
+        assert_eq!(inputs.axis(AxisIndex::Rotate), 0.0);
+        inputs.action_up_at(InputIndex::Left, 40.0);
+        inputs.action_up_at(InputIndex::Right, 40.0);
+        inputs.begin_tick(60.0, &config);
+        assert_close(inputs.axis(AxisIndex::Rotate), 1.0);
+    }
+
+    #[test]
+    fn keys_act_in_the_active_context() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        assert!(inputs.key_down(KeyCode::Space, 0.0, &config));
+        assert!(inputs.is_down(InputIndex::Shoot));
+        assert!(!inputs.is_down(InputIndex::Confirm));
+        inputs.key_up(KeyCode::Space, 0.0, &config);
+
+        inputs.set_context(InputContext::Menu);
+        assert!(inputs.key_down(KeyCode::Space, 0.0, &config));
+        assert!(inputs.is_down(InputIndex::Confirm));
+        assert!(!inputs.is_down(InputIndex::Shoot));
+        assert!(!inputs.key_down(KeyCode::ArrowLeft, 0.0, &config));
+
+        // actions of other contexts can't be pressed directly either
+        inputs.action_down(InputIndex::Shoot);
+        assert!(!inputs.been_pressed(InputIndex::Shoot));
+    }
+
+    #[test]
+    fn switching_context_releases_held_inputs() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.key_down(KeyCode::Escape, 0.0, &config);
+        inputs.set_context(InputContext::Paused);
+        assert!(!inputs.is_down(InputIndex::Pause));
+        assert!(!inputs.just_pressed(InputIndex::Pause));
+        // the key still held doesn't resume until pressed again
+        assert!(!inputs.just_pressed(InputIndex::Resume));
+        inputs.key_up(KeyCode::Escape, 10.0, &config);
+        assert!(!inputs.just_released(InputIndex::Resume));
+        inputs.key_down(KeyCode::Escape, 20.0, &config);
+        assert!(inputs.just_pressed(InputIndex::Resume));
+
+        // setting the same context keeps inputs held
+        inputs.set_context(InputContext::Paused);
+        assert!(inputs.is_down(InputIndex::Resume));
+    }
+
+    #[test]
+    fn edges_last_until_the_next_tick() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.key_down(KeyCode::Space, 0.0, &config);
+        assert!(inputs.just_pressed(InputIndex::Shoot));
+        // repeats aren't presses
+        inputs.tick();
+        inputs.key_down(KeyCode::Space, 10.0, &config);
+        assert!(!inputs.just_pressed(InputIndex::Shoot));
+        assert!(inputs.is_down(InputIndex::Shoot));
+        inputs.key_up(KeyCode::Space, 20.0, &config);
+        assert!(inputs.just_released(InputIndex::Shoot));
+        inputs.tick();
+        assert!(!inputs.just_released(InputIndex::Shoot));
+
+        // a tap within a tick is both pressed and released, and still seen
+        inputs.key_down(KeyCode::Space, 30.0, &config);
+        inputs.key_up(KeyCode::Space, 31.0, &config);
+        assert!(inputs.just_pressed(InputIndex::Shoot) && inputs.just_released(InputIndex::Shoot));
+        assert!(inputs.been_pressed(InputIndex::Shoot) && !inputs.is_down(InputIndex::Shoot));
+        inputs.tick();
+        assert!(!inputs.been_pressed(InputIndex::Shoot));
+    }
+
+    #[test]
+    fn mouse_only_acts_when_aiming_with_it() {
+        let mut config = config();
+        let mut inputs = Inputs::new();
+        inputs.mouse_move(Vec2D { x: 10.0, y: 20.0 }, &config);
+        inputs.mouse_down(0, 0.0, &config);
+        assert_eq!(inputs.aim(), None);
+        assert!(!inputs.is_down(InputIndex::Shoot));
+
+        config.control_scheme = ControlScheme::MouseAim;
+        inputs.mouse_move(Vec2D { x: 10.0, y: 20.0 }, &config);
+        inputs.mouse_down(0, 0.0, &config);
+        inputs.mouse_down(2, 0.0, &config);
+        assert_eq!(inputs.aim(), Some(Vec2D { x: 10.0, y: 20.0 }));
+        assert!(inputs.is_down(InputIndex::Shoot) && inputs.is_down(InputIndex::Forward));
+
+        // switching schemes lets go of the mouse
+        inputs.release_mouse(&config);
+        assert_eq!(inputs.aim(), None);
+        assert!(!inputs.is_down(InputIndex::Shoot) && !inputs.is_down(InputIndex::Forward));
+    }
+
+    #[test]
+    fn partial_press_within_a_tick() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.begin_tick(0.0, &config);
+        inputs.action_down_at(InputIndex::Forward, 5.0);
+        inputs.action_up_at(InputIndex::Forward, 10.0);
+        inputs.begin_tick(20.0, &config);
+        assert_held(&inputs, 0.25);
+        assert!(inputs.just_pressed(InputIndex::Forward) && inputs.just_released(InputIndex::Forward));
+
+        // the same tap between frames 10ms apart is as strong
+        inputs.tick();
+        inputs.action_down_at(InputIndex::Forward, 22.0);
+        inputs.action_up_at(InputIndex::Forward, 27.0);
+        inputs.begin_tick(30.0, &config);
+        assert_held(&inputs, 0.25);
+    }
+
+    #[test]
+    fn tap_before_the_first_tick() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.action_down_at(InputIndex::Forward, 105.0);
+        inputs.action_up_at(InputIndex::Forward, 110.0);
+        inputs.begin_tick(120.0, &config);
+        assert_held(&inputs, 0.25);
+
+        // untimed presses before the first tick still count fully
+        let mut inputs = Inputs::new();
+        inputs.action_down(InputIndex::Forward);
+        inputs.begin_tick(120.0, &config);
+        assert_held(&inputs, 1.0);
+    }
+
+    #[test]
+    fn press_spanning_ticks() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.begin_tick(0.0, &config);
+        inputs.action_down_at(InputIndex::Forward, 15.0);
+        inputs.begin_tick(20.0, &config);
+        assert_held(&inputs, 0.25);
+        inputs.tick();
+        inputs.begin_tick(40.0, &config);
+        assert_held(&inputs, 1.0);
+        inputs.tick();
+        inputs.action_up_at(InputIndex::Forward, 50.0);
+        inputs.begin_tick(60.0, &config);
+        assert_held(&inputs, 0.5);
+        inputs.tick();
+        inputs.begin_tick(80.0, &config);
+        assert_held(&inputs, 0.0);
+    }
+
+    #[test]
+    fn throttled_frames_hold_at_most_a_tick() {
+        let config = config();
+        let mut inputs = Inputs::new();
+        inputs.begin_tick(0.0, &config);
+        inputs.action_down_at(InputIndex::Forward, 10.0);
+        inputs.begin_tick(100.0, &config);
+        assert_held(&inputs, 1.0);
+        assert_eq!(inputs.axis(AxisIndex::Thrust), 1.0);
+    }
 }
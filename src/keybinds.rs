@@ -0,0 +1,196 @@
+use crate::input::{InputIndex, InputContext, ALL_INPUTS};
+use crate::keycode::{KeyCode};
+
+pub const DEFAULT_KEYBINDS: &[(KeyCode, InputIndex)] = &[
+    // gameplay
+    (KeyCode::KeyZ, InputIndex::Shoot),
+    (KeyCode::Space, InputIndex::Shoot),
+    (KeyCode::ArrowUp, InputIndex::Forward),
+    (KeyCode::ArrowDown, InputIndex::Backward),
+    (KeyCode::ArrowLeft, InputIndex::Left),
+    (KeyCode::ArrowRight, InputIndex::Right),
+    (KeyCode::Escape, InputIndex::Pause),
+    (KeyCode::KeyP, InputIndex::Pause),
+    // menu
+    (KeyCode::KeyZ, InputIndex::Confirm),
+    (KeyCode::Space, InputIndex::Confirm),
+    (KeyCode::Enter, InputIndex::Confirm),
+    // paused
+    (KeyCode::Escape, InputIndex::Resume),
+    (KeyCode::KeyP, InputIndex::Resume),
+    // text entry
+    (KeyCode::Enter, InputIndex::Submit),
+    (KeyCode::Escape, InputIndex::Cancel),
+    (KeyCode::Backspace, InputIndex::Erase),
+];
+
+/*
+ *     An action may have any number of keys bound to it, and nothing stops
+ * a key from being bound to several actions. Actions from different input
+ * contexts never fire together, so only keys bound to several actions of
+ * the same context are reported as conflicts. They aren't rejected, so that
+ * the player can finish rebinding the other action before the conflict
+ * is resolved.
+ */
+#[derive(Clone)]
+pub struct KeyBinds {
+    binds: Vec<(KeyCode, InputIndex)>,
+}
+
+impl KeyBinds {
+    pub fn new() -> KeyBinds {
+        KeyBinds {
+            binds: DEFAULT_KEYBINDS.to_vec(),
+        }
+    }
+
+    pub fn empty() -> KeyBinds {
+        KeyBinds {
+            binds: Vec::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = KeyBinds::new();
+    }
+
+    pub fn lookup(&self, code: KeyCode, context: InputContext) -> Option<InputIndex> {
+        self.binds.iter()
+            .find(|&&(key, input)| key == code && input.context() == context)
+            .map(|&(_, input)| input)
+    }
+
+    pub fn keys_for(&self, input: InputIndex) -> Vec<KeyCode> {
+        self.binds.iter()
+            .filter(|&&(_, bound)| bound == input)
+            .map(|&(key, _)| key)
+            .collect()
+    }
+
+    /// Adds `code` to the keys of `input`.
+    /// Returns the other action of the same context the key is already bound to, if any.
+    pub fn bind(&mut self, code: KeyCode, input: InputIndex) -> Option<InputIndex> {
+        let conflict = self.binds.iter()
+            .find(|&&(key, bound)| key == code && bound != input && bound.context() == input.context())
+            .map(|&(_, bound)| bound);
+        if !self.binds.contains(&(code, input)) {
+            self.binds.push((code, input));
+        }
+        conflict
+    }
+
+    pub fn unbind(&mut self, code: KeyCode, input: InputIndex) -> bool {
+        let len = self.binds.len();
+        self.binds.retain(|&bind| bind != (code, input));
+        self.binds.len() != len
+    }
+
+    pub fn clear(&mut self, input: InputIndex) {
+        self.binds.retain(|&(_, bound)| bound != input);
+    }
+
+    /// Lists keys bound to more than one action of the same context,
+    /// with the actions they're bound to.
+    pub fn conflicts(&self) -> Vec<(KeyCode, Vec<InputIndex>)> {
+        let mut rv: Vec<(KeyCode, Vec<InputIndex>)> = Vec::new();
+        for &(key, input) in self.binds.iter() {
+            let same = |entry: &&mut (KeyCode, Vec<InputIndex>)| {
+                entry.0 == key && entry.1[0].context() == input.context()
+            };
+            if let Some(entry) = rv.iter_mut().find(same) {
+                entry.1.push(input);
+                continue;
+            }
+            rv.push((key, vec![input]));
+        }
+        rv.retain(|entry| entry.1.len() > 1);
+        rv
+    }
+
+    /// Serializes bindings as `action=code` pairs separated by commas,
+    /// e.g. `shoot=KeyZ,shoot=Space,forward=ArrowUp`.
+    /// Actions without keys are written as `action=`, so that they stay unbound
+    /// instead of getting default keys when loaded.
+    pub fn serialize(&self) -> String {
+        let mut pairs: Vec<String> = self.binds.iter()
+            .map(|&(key, input)| format!("{}={}", input.name(), key.as_str()))
+            .collect();
+        for input in ALL_INPUTS.iter() {
+            if self.keys_for(*input).is_empty() {
+                pairs.push(format!("{}=", input.name()));
+            }
+        }
+        pairs.join(",")
+    }
+
+    /// Actions missing from `s`, e.g. ones added in a later version,
+    /// get their default keys. Bindings saved with numeric key codes
+    /// by older versions aren't understood, so defaults are used instead.
+    pub fn deserialize(s: &str) -> Option<KeyBinds> {
+        let mut binds = KeyBinds::empty();
+        let mut seen = Vec::new();
+        for pair in s.split(',').filter(|pair| !pair.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let input = InputIndex::from_name(parts.next()?)?;
+            seen.push(input);
+            let key = parts.next()?;
+            if !key.is_empty() {
+                binds.bind(KeyCode::from_code(key)?, input);
+            }
+        }
+        for &(key, input) in DEFAULT_KEYBINDS.iter() {
+            if !seen.contains(&input) {
+                binds.bind(key, input);
+            }
+        }
+        Some(binds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialization_round_trips() {
+        let mut binds = KeyBinds::new();
+        binds.clear(InputIndex::Shoot);
+        binds.bind(KeyCode::KeyX, InputIndex::Shoot);
+        binds.clear(InputIndex::Erase);
+        let s = binds.serialize();
+        assert!(s.contains("shoot=KeyX"));
+        assert!(s.contains("erase="));
+
+        let loaded = KeyBinds::deserialize(&s).unwrap();
+        assert_eq!(loaded.serialize(), s);
+        assert_eq!(loaded.keys_for(InputIndex::Shoot), vec![KeyCode::KeyX]);
+        // unbound on purpose, not reset to the defaults
+        assert!(loaded.keys_for(InputIndex::Erase).is_empty());
+    }
+
+    #[test]
+    fn missing_actions_get_defaults() {
+        let loaded = KeyBinds::deserialize("shoot=KeyX").unwrap();
+        assert_eq!(loaded.keys_for(InputIndex::Shoot), vec![KeyCode::KeyX]);
+        assert_eq!(loaded.keys_for(InputIndex::Forward), KeyBinds::new().keys_for(InputIndex::Forward));
+        assert!(KeyBinds::deserialize("shoot=90").is_none());
+        assert!(KeyBinds::deserialize("jump=KeyX").is_none());
+    }
+
+    #[test]
+    fn conflicts_are_within_a_context() {
+        let mut binds = KeyBinds::new();
+        // Space is Shoot in gameplay and Confirm in menus, which is fine
+        assert!(binds.conflicts().is_empty());
+
+        assert_eq!(binds.bind(KeyCode::Space, InputIndex::Forward), Some(InputIndex::Shoot));
+        assert_eq!(binds.conflicts(), vec![(KeyCode::Space, vec![InputIndex::Shoot, InputIndex::Forward])]);
+        // still bound, so the player can rebind Shoot next
+        assert_eq!(binds.lookup(KeyCode::Space, InputContext::Gameplay), Some(InputIndex::Shoot));
+
+        assert!(binds.unbind(KeyCode::Space, InputIndex::Shoot));
+        assert!(binds.conflicts().is_empty());
+        assert_eq!(binds.lookup(KeyCode::Space, InputContext::Gameplay), Some(InputIndex::Forward));
+        assert!(!binds.unbind(KeyCode::Space, InputIndex::Shoot));
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ *     Keys are identified by `KeyboardEvent.code`, which names the physical
+ * key and doesn't depend on the keyboard layout: `KeyZ` is the key left of
+ * `KeyX` both on QWERTY and AZERTY. What's written on the key comes from
+ * `KeyboardEvent.key`, and is only used for displaying key names.
+ */
+macro_rules! key_codes {
+    ($($name:ident,)*) => {
+        #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+        pub enum KeyCode {
+            $($name,)*
+        }
+
+        const ALL_KEY_CODES: &[KeyCode] = &[
+            $(KeyCode::$name,)*
+        ];
+
+        impl KeyCode {
+            /// The `KeyboardEvent.code` value for this key.
+            pub fn as_str(&self) -> &'static str {
+                match *self {
+                    $(KeyCode::$name => stringify!($name),)*
+                }
+            }
+
+            pub fn from_code(code: &str) -> Option<KeyCode> {
+                match code {
+                    $(stringify!($name) => Some(KeyCode::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+key_codes! {
+    Unidentified,
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+    KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+    Space, Enter, Escape, Backspace, Tab, CapsLock,
+    ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight, MetaLeft, MetaRight,
+    Insert, Delete, Home, End, PageUp, PageDown, ContextMenu,
+    Minus, Equal, BracketLeft, BracketRight, Backslash, IntlBackslash,
+    Semicolon, Quote, Backquote, Comma, Period, Slash,
+}
+
+impl KeyCode {
+    pub fn from_u32(idx: u32) -> Option<KeyCode> {
+        ALL_KEY_CODES.get(idx as usize).cloned()
+    }
+
+    /// Key codes not listed above are `Unidentified`.
+    pub fn parse(code: &str) -> KeyCode {
+        KeyCode::from_code(code).unwrap_or(KeyCode::Unidentified)
+    }
+
+    /// A name for keys whose layout-specific name is unknown.
+    pub fn default_name(&self) -> &'static str {
+        let code = self.as_str();
+        match *self {
+            KeyCode::ArrowUp => "Up",
+            KeyCode::ArrowDown => "Down",
+            KeyCode::ArrowLeft => "Left",
+            KeyCode::ArrowRight => "Right",
+            KeyCode::Escape => "Esc",
+            _ if code.starts_with("Key") => &code[3..],
+            _ if code.starts_with("Digit") => &code[5..],
+            _ => code,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub fn any(&self) -> bool {
+        self.shift || self.ctrl || self.alt || self.meta
+    }
+}
+
+/// Names of keys in the player's keyboard layout, learned from key events.
+pub struct KeyNames {
+    names: Vec<(KeyCode, String)>,
+}
+
+impl KeyNames {
+    pub fn new() -> KeyNames {
+        KeyNames {
+            names: Vec::new(),
+        }
+    }
+
+    /// Remembers what `KeyboardEvent.key` the key produces.
+    /// Only printable keys are remembered, others have fine default names.
+    pub fn learn(&mut self, code: KeyCode, key: &str) {
+        if code == KeyCode::Unidentified || key.chars().count() != 1 || key == " " {
+            return;
+        }
+        let name = key.to_uppercase();
+        match self.names.iter_mut().find(|entry| entry.0 == code) {
+            Some(entry) => entry.1 = name,
+            None => self.names.push((code, name)),
+        }
+    }
+
+    pub fn name(&self, code: KeyCode) -> &str {
+        self.names.iter()
+            .find(|entry| entry.0 == code)
+            .map_or(code.default_name(), |entry| &entry.1)
+    }
+}
@@ -1,68 +1,356 @@
 pub mod alloc;
+pub mod host;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod wasm_host;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod native_host;
 pub mod eventloop;
+pub mod executor;
 mod time;
 mod rng;
-mod geom;
-mod math;
+pub mod geom;
+pub mod math;
+pub mod numeric;
 mod ship;
 mod input;
+mod keycode;
+mod keybinds;
+mod gamepad;
+mod touch;
+mod storage;
+mod payload;
 mod game;
+pub mod render;
 mod render_path;
+mod viewport;
+mod flow;
+pub mod sim;
+pub mod svg_export;
+pub mod theme;
+pub mod agent;
+pub mod api;
 
-extern "C" {
-    #[allow(dead_code)]
-    fn alert(n: f64);
-    fn puts(ptr: *const u8, len: usize);
-    fn svg_set_path(ptr: *const u8, len: usize);
+fn putstr(s: &str) {
+    host().puts(s);
 }
 
-fn putstr(s: &str) {
-    unsafe { puts(s.as_ptr(), s.len()) };
+fn update_svg(layers: &SvgLayers) {
+    let host = host();
+    for &style in Style::ALL.iter() {
+        host.svg_set_path(style as u32, layers.path(style));
+    }
+}
+
+fn clear_svg() {
+    update_svg(&SvgLayers::new());
+}
+
+// the host draws both render targets with these styles
+fn apply_theme(theme: &Theme) {
+    let host = host();
+    host.svg_set_background(theme.background);
+    for &style in Style::ALL.iter() {
+        let layer = theme.layer(style);
+        host.svg_set_style(style as u32, layer.stroke, layer.width, layer.opacity);
+    }
+}
+
+fn current_theme() -> &'static Theme {
+    THEME.with(|theme| theme.get())
+}
+
+fn set_theme(theme: &'static Theme) {
+    THEME.with(|current| current.set(theme));
+    apply_theme(theme);
+    storage::save(THEME_STORAGE_KEY, theme.name);
+}
+
+fn load_theme() -> &'static Theme {
+    storage::load(THEME_STORAGE_KEY)
+        .and_then(|name| Theme::by_name(&name))
+        .unwrap_or(&theme::CLASSIC)
+}
+
+fn render_frame(game: &Game) {
+    match RENDER_TARGET.with(|target| target.get()) {
+        RenderTarget::SvgPath => {
+            let mut layers = SvgLayers::new();
+            render_game(&mut layers, game);
+            update_svg(&layers);
+        },
+        RenderTarget::DisplayList => DISPLAY_LIST.with(|list| {
+            let mut list = list.borrow_mut();
+            list.clear();
+            list.viewport(&game.viewport);
+            render_game(&mut *list, game);
+            host().draw_display_list(&list.bytes);
+        }),
+    }
 }
 
-fn update_svg(s: &str) {
-    unsafe { svg_set_path(s.as_ptr(), s.len()) };
+// clears what the other target has drawn, the next frame draws with `target`
+fn set_render_target(target: RenderTarget) {
+    let previous = RENDER_TARGET.with(|current| current.replace(target));
+    match previous {
+        _ if previous == target => {},
+        RenderTarget::SvgPath => clear_svg(),
+        RenderTarget::DisplayList => host().draw_display_list(&[]),
+    }
 }
 
+// anything outside of the scene, e.g. asteroids wrapping around, is clipped
+fn update_viewport(viewport: &Viewport) {
+    let (origin, size) = viewport.scene_rect();
+    let host = host();
+    host.svg_set_transform(&viewport.svg_transform());
+    host.svg_set_clip(origin.x, origin.y, size.x, size.y);
+}
+
+use std::cell::{Cell, RefCell};
+
+use host::{host};
 use time::{Instant, Duration};
 use eventloop::{Event, EventLoop};
+use executor::{Executor};
+use eventloop::{EVENTS_MOUSE_MOVE, EVENTS_MOUSE_BUTTONS, EVENTS_KEYBOARD, EVENTS_TOUCH, EVENTS_PAGE, EVENTS_TEXT};
 
-use game::{Game};
+use game::{Game, Config, ControlScheme, MathMode};
+use rng::{seeded_rng};
+use keybinds::{KeyBinds};
+use render::{RenderTarget, Style, SvgLayers, DisplayList};
+use theme::{Theme};
 use render_path::{render_game};
+use viewport::{Viewport};
+
+const KEYBINDS_STORAGE_KEY: &str = "asteroids.keybinds";
+const CONTROL_SCHEME_STORAGE_KEY: &str = "asteroids.control_scheme";
+const THEME_STORAGE_KEY: &str = "asteroids.theme";
+
+/*
+ *     Browsers throttle animation frames, e.g. when the page is in a background
+ * tab or the machine is busy, which would slow the game down. A timer checks
+ * how long ago the game has ticked, and catches up when frames stall.
+ * Frames and catch-ups share one clock, `last_tick` only moves forward, and
+ * a frame with a timestamp older than the last catch-up doesn't tick again.
+ *
+ *     That only covers stalls while the page is visible, `Game::tick` does
+ * nothing while it's hidden. The clock is reset when the page is hidden or
+ * shown, so coming back to the page doesn't play a burst of catch-up ticks
+ * for the time it was away.
+ */
+const STALL_CHECK_INTERVAL: u64 = 100;
+const STALL_THRESHOLD_MS: f64 = 50.0;
+const MAX_CATCH_UP_TICKS: u32 = 6;
+
+thread_local! {
+    static GAME: RefCell<Option<Game>> = const { RefCell::new(None) };
+    // dropping the event loop shuts it down
+    static EVENT_LOOP: RefCell<Option<EventLoop>> = const { RefCell::new(None) };
+    static RENDER_TARGET: Cell<RenderTarget> = const { Cell::new(RenderTarget::SvgPath) };
+    static THEME: Cell<&'static Theme> = const { Cell::new(&theme::CLASSIC) };
+    // kept between frames, so that its memory is reused
+    static DISPLAY_LIST: RefCell<DisplayList> = const { RefCell::new(DisplayList { bytes: Vec::new() }) };
+}
+
+fn with_game<F, R>(f: F) -> Option<R>
+    where F: FnOnce(&mut Game) -> R
+{
+    GAME.with(|game| game.borrow_mut().as_mut().map(f))
+}
+
+fn load_key_binds() -> KeyBinds {
+    storage::load(KEYBINDS_STORAGE_KEY)
+        .and_then(|s| KeyBinds::deserialize(&s))
+        .unwrap_or_else(KeyBinds::new)
+}
+
+fn save_key_binds(key_binds: &KeyBinds) {
+    storage::save(KEYBINDS_STORAGE_KEY, &key_binds.serialize());
+}
+
+fn load_control_scheme() -> ControlScheme {
+    storage::load(CONTROL_SCHEME_STORAGE_KEY)
+        .and_then(|s| s.parse().ok())
+        .and_then(ControlScheme::from_u32)
+        .unwrap_or(ControlScheme::Keyboard)
+}
+
+fn save_control_scheme(scheme: ControlScheme) {
+    storage::save(CONTROL_SCHEME_STORAGE_KEY, &(scheme as u32).to_string());
+}
 
 fn duration_to_ms(duration: &Duration) -> f64 {
     (duration.as_secs() as f64) * 1e3 + (duration.subsec_nanos() as f64) / 1e6
 }
+// mouse moves only matter when aiming with the mouse
+fn game_events(scheme: ControlScheme) -> u32 {
+    let events = EVENTS_MOUSE_BUTTONS | EVENTS_KEYBOARD | EVENTS_TOUCH | EVENTS_PAGE | EVENTS_TEXT;
+    match scheme {
+        ControlScheme::MouseAim => events | EVENTS_MOUSE_MOVE,
+        ControlScheme::Keyboard => events,
+    }
+}
+
+fn update_subscriptions(scheme: ControlScheme) {
+    EVENT_LOOP.with(|event_loop| {
+        if let Some(event_loop) = event_loop.borrow_mut().as_mut() {
+            event_loop.unsubscribe(!game_events(scheme));
+            event_loop.subscribe(game_events(scheme));
+        }
+    });
+}
+
+/// Stops the running game, if any. The page may start a new one with `my_main`.
+#[no_mangle]
+pub extern "C"
+fn stop_game() {
+    let event_loop = EVENT_LOOP.with(|event_loop| event_loop.borrow_mut().take());
+    drop(event_loop);
+    if GAME.with(|game| game.borrow_mut().take()).is_some() {
+        clear_svg();
+        host().draw_display_list(&[]);
+    }
+}
+
 #[no_mangle]
 pub extern "C"
 fn my_main() {
-    let mut game = Box::new(Game::new());
+    start_game(None);
+}
+
+/// Starts a game, stopping the running one. With a `seed` the game uses
+/// `MathMode::Deterministic` and a seeded rng, like the simulation does.
+fn start_game(seed: Option<u64>) {
+    stop_game();
+    let mut config = Config::new();
+    config.key_binds = load_key_binds();
+    let control_scheme = load_control_scheme();
+    config.control_scheme = control_scheme;
+    let game = match seed {
+        Some(seed) => {
+            config.math = MathMode::Deterministic;
+            Game::with_rng(config, seeded_rng(seed))
+        },
+        None => Game::with_config(config),
+    };
+    GAME.with(|current| *current.borrow_mut() = Some(game));
+    let theme = load_theme();
+    THEME.with(|current| current.set(theme));
+    apply_theme(theme);
+    // until the host tells the actual screen size
+    with_game(|game| update_viewport(&game.viewport));
 
     let _start = Instant::now();
+    let mut last_tick = None;
+
+    /*
+     *     The flow scripts run after the game has ticked, so that they see
+     * the state and input edges of the frame that has just been played.
+     */
+    let executor = Executor::new();
+    executor.spawn(flow::game_flow());
+
+    // started with the first frame
+    let mut stall_timer = None;
 
     let mut event_loop = EventLoop::new(Box::new(move |event, event_loop| {
-        let game = game.as_mut();
-        match event {
+        with_game(|game| match event {
             Event::Destroyed => {},
-            Event::MouseMove { x: _, y: _ } => {
-                //putstr(&format!("x: {}, y: {}", x, y));
+            Event::MouseMove { x, y } => {
+                game.inputs.mouse_move(game.viewport.screen_to_field(x, y), &game.config);
+            },
+            Event::MouseDown { x, y, button, time } => {
+                game.inputs.mouse_move(game.viewport.screen_to_field(x, y), &game.config);
+                game.inputs.mouse_down(button, time, &game.config);
+            },
+            Event::MouseUp { x: _, y: _, button, time } => {
+                game.inputs.mouse_up(button, time, &game.config);
             },
-            Event::KeyDown { code, chr: _, flags: _ } => {
-                game.inputs.key_down(code, &game.config);
+            Event::Wheel { .. } => {},
+            Event::PointerDown { .. } | Event::PointerMove { .. } | Event::PointerUp { .. } => {},
+            Event::TouchStart { touches, time: _ } => {
+                for touch in touches {
+                    let pos = game.viewport.screen_to_field(touch.x, touch.y);
+                    game.touch.touch_start(touch.id, pos, &mut game.inputs, &game.config);
+                }
+            },
+            Event::TouchMove { touches, time: _ } => {
+                for touch in touches {
+                    let pos = game.viewport.screen_to_field(touch.x, touch.y);
+                    game.touch.touch_move(touch.id, pos, &mut game.inputs, &game.config);
+                }
+            },
+            Event::TouchEnd { touches, time: _ } => {
+                for touch in touches {
+                    game.touch.touch_end(touch.id, &mut game.inputs, &game.config);
+                }
+            },
+            Event::TextInput { text, time: _ } => {
+                game.text_input(&text);
+            },
+            Event::Message { .. } => {},
+            Event::KeyDown { code, key, modifiers, repeat, time } => {
+                if game.key_down(code, &key, modifiers, repeat, time) {
+                    save_key_binds(&game.config.key_binds);
+                }
             },
-            Event::KeyUp { code, chr: _, flags: _ } => {
-                game.inputs.key_up(code, &game.config);
+            Event::KeyUp { code, key: _, modifiers: _, time } => {
+                game.key_up(code, time);
             },
-            Event::AnimationFrame => {
+            Event::Gamepad { index, buttons, axes, button_values } => {
+                let analog = gamepad::analog_values(&axes, &button_values);
+                game.inputs.gamepad(index, buttons, &analog, &game.config);
+            },
+            Event::Resize { width, height, dpr } => {
+                game.resize(f64::from(width), f64::from(height), dpr);
+                update_viewport(&game.viewport);
+            },
+            Event::VisibilityChange { visible } => {
+                game.set_hidden(!visible);
+                // the clock restarts with the next frame
+                last_tick = None;
+            },
+            Event::Focus => {},
+            Event::Blur => {
+                game.focus_lost();
+            },
+            Event::Timer { handle, time } if Some(handle) == stall_timer => {
+                let last = match last_tick {
+                    Some(last) => last,
+                    None => return,
+                };
+                if time - last < STALL_THRESHOLD_MS {
+                    return;
+                }
+                let tick_ms = game.config.delta_t * 1e3;
+                let ticks = (((time - last) / tick_ms) as u32).min(MAX_CATCH_UP_TICKS);
+                for i in 1..ticks + 1 {
+                    game.tick(last + tick_ms * f64::from(i));
+                }
+                if let Some(rumble) = game.rumble.take() {
+                    rumble.play();
+                }
+                last_tick = Some(time);
+            },
+            Event::Timer { .. } => {},
+            Event::AnimationFrame { time } => {
 
+                if stall_timer.is_none() {
+                    stall_timer = Some(event_loop.set_interval(Duration::from_millis(STALL_CHECK_INTERVAL)));
+                }
                 let frame_start = Instant::now();
-                game.tick();
+                // a catch-up may have played this frame's time already
+                if last_tick.is_none_or(|last| time > last) {
+                    game.tick(time);
+                    last_tick = Some(time);
+                }
+                if let Some(rumble) = game.rumble.take() {
+                    rumble.play();
+                }
                 let tick_time = frame_start.elapsed();
 
                 let render_start = Instant::now();
-                let mut buf = String::new();
-                render_game(&mut buf, game);
-                update_svg(&buf);
+                render_frame(game);
                 let render_time = render_start.elapsed();
                 let frame_time = frame_start.elapsed();
 
@@ -75,8 +363,172 @@ fn my_main() {
 
                 event_loop.request_animation_frame();
             },
-        }
+        });
     }));
+    event_loop.add_handler(executor.into_handler());
     putstr("event loop started");
+    event_loop.subscribe(game_events(control_scheme));
     event_loop.request_animation_frame();
+    EVENT_LOOP.with(|el| *el.borrow_mut() = Some(event_loop));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{GameState};
+    use keycode::{KeyCode, Modifiers};
+    use native_host::{NativeHost};
+    use std::rc::{Rc};
+
+    const FRAME_MS: f64 = 16.0;
+
+    // runs frames until `time`, returns the time of the last frame
+    fn run_until(host: &Rc<NativeHost>, mut time: f64, until: f64) -> f64 {
+        while time < until {
+            time += FRAME_MS;
+            host.advance(time);
+        }
+        time
+    }
+
+    #[test]
+    fn game_renders_on_the_native_host() {
+        let host = native_host::default_host();
+        my_main();
+        run_until(&host, 0.0, 10.0 * FRAME_MS);
+        assert!(with_game(|game| game.tick).unwrap() > 0);
+        let svg = host.svg();
+        assert!(!svg.layers[Style::Ship as usize].path.is_empty());
+        assert!(!svg.layers[Style::Asteroid as usize].path.is_empty());
+        assert!(svg.transform.starts_with("translate("));
+
+        stop_game();
+        assert!(!host.svg().is_drawn());
+        assert!(with_game(|game| game.tick).is_none());
+    }
+
+    fn game_tick() -> u64 {
+        with_game(|game| game.tick).unwrap()
+    }
+
+    #[test]
+    fn stalled_frames_are_caught_up_on_one_clock() {
+        let host = native_host::default_host();
+        my_main();
+        let time = run_until(&host, 0.0, 10.0 * FRAME_MS);
+        let tick = game_tick();
+        // frames stall for 500ms, the stall timer plays the ticks meanwhile
+        host.advance_timers(time + 500.0);
+        let caught_up = game_tick();
+        assert!(caught_up >= tick + 25, "only caught up {} ticks", caught_up - tick);
+        // a frame timestamped before the last catch-up has been played already
+        host.advance(time + 450.0);
+        assert_eq!(game_tick(), caught_up);
+        host.advance(time + 500.0 + FRAME_MS);
+        assert_eq!(game_tick(), caught_up + 1);
+        stop_game();
+    }
+
+    #[test]
+    fn showing_the_page_again_doesnt_catch_up() {
+        let host = native_host::default_host();
+        my_main();
+        let time = run_until(&host, 0.0, 10.0 * FRAME_MS);
+        host.set_visible(false);
+        // hidden pages get no frames, timers keep firing
+        host.advance_timers(time + 5000.0);
+        let tick = game_tick();
+        host.set_visible(true);
+        // as if the player resumed right away, paused games don't count ticks
+        with_game(|game| game.game_state = GameState::Running);
+        host.advance_timers(time + 5100.0);
+        host.advance(time + 5100.0 + FRAME_MS);
+        assert!(game_tick() <= tick + 2, "played {} ticks", game_tick() - tick);
+        stop_game();
+    }
+
+    #[test]
+    fn game_renders_a_display_list() {
+        let host = native_host::default_host();
+        my_main();
+        set_render_target(RenderTarget::DisplayList);
+        let time = run_until(&host, 0.0, 10.0 * FRAME_MS);
+        let list = host.display_list();
+        assert_eq!(list[0], render::DISPLAY_LIST_VIEWPORT);
+        assert!(list.contains(&render::DISPLAY_LIST_POLYLINE));
+
+        set_render_target(RenderTarget::SvgPath);
+        assert!(host.display_list().is_empty());
+        run_until(&host, time, time + FRAME_MS);
+        assert!(host.svg().is_drawn());
+        stop_game();
+    }
+
+    #[test]
+    fn themes_style_the_layers() {
+        let host = native_host::default_host();
+        my_main();
+        let svg = host.svg();
+        assert_eq!(svg.background, "black");
+        assert_eq!(svg.layers.len(), render::LAYER_COUNT);
+        assert!(svg.layers.iter().all(|layer| layer.stroke == "white"));
+
+        set_theme(&theme::NEON);
+        let bullets = &host.svg().layers[Style::Bullet as usize];
+        assert_eq!((bullets.stroke.as_str(), bullets.width), ("#ff2bd6", 3.0));
+
+        // remembered for the next game
+        my_main();
+        assert_eq!(host.svg().background, theme::NEON.background);
+        stop_game();
+    }
+
+    #[test]
+    fn next_level_starts_after_the_intro() {
+        let host = native_host::default_host();
+        my_main();
+        let time = run_until(&host, 0.0, FRAME_MS);
+        let level = with_game(|game| {
+            game.asteroids.clear();
+            game.level
+        }).unwrap();
+        let time = run_until(&host, time, time + 5.0 * FRAME_MS);
+        with_game(|game| {
+            assert!(game.game_state == GameState::LevelCleared);
+            assert_eq!(game.banner, Some(level));
+        });
+
+        // confirming during the intro doesn't count
+        with_game(|game| game.key_down(KeyCode::Enter, "Enter", Modifiers::default(), false, time));
+        with_game(|game| game.key_up(KeyCode::Enter, time));
+        let time = run_until(&host, time, time + 3000.0);
+        assert!(with_game(|game| game.game_state == GameState::LevelCleared).unwrap());
+
+        with_game(|game| game.key_down(KeyCode::Enter, "Enter", Modifiers::default(), false, time));
+        run_until(&host, time, time + 2.0 * FRAME_MS);
+        with_game(|game| {
+            assert!(game.game_state == GameState::Running);
+            assert_eq!(game.level, level + 1);
+            assert_eq!(game.asteroids.len(), level + 1);
+            assert_eq!(game.banner, None);
+        });
+        stop_game();
+    }
+
+    #[test]
+    fn level_intro_stops_while_hidden() {
+        let host = native_host::default_host();
+        my_main();
+        let time = run_until(&host, 0.0, FRAME_MS);
+        with_game(|game| game.asteroids.clear());
+        let time = run_until(&host, time, time + 5.0 * FRAME_MS);
+        host.set_visible(false);
+        let time = run_until(&host, time, time + 3000.0);
+        host.set_visible(true);
+        // still blinking, so confirming doesn't count yet
+        with_game(|game| game.key_down(KeyCode::Enter, "Enter", Modifiers::default(), false, time));
+        run_until(&host, time, time + 2.0 * FRAME_MS);
+        assert!(with_game(|game| game.game_state == GameState::LevelCleared).unwrap());
+        stop_game();
+    }
 }
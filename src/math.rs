@@ -1,10 +1,16 @@
-use std::ops::{Add, Sub, SubAssign, AddAssign};
+use std::ops::{Add, Sub, SubAssign, AddAssign, Mul, Div, Neg};
 
 pub fn clip(x: f64, max: f64) -> f64 {
     x - (x / max).floor() * max
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Wraps an angle into range -PI..PI.
+pub fn wrap_angle(angle: f64) -> f64 {
+    use std::f64::consts::PI;
+    clip(angle + PI, 2.0 * PI) - PI
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vec2D {
     pub x: f64,
     pub y: f64,
@@ -44,6 +50,33 @@ impl SubAssign for Vec2D {
     }
 }
 
+impl Mul<f64> for Vec2D {
+    type Output = Vec2D;
+    fn mul(self, factor: f64) -> Vec2D {
+        self.scale(factor)
+    }
+}
+
+impl Div<f64> for Vec2D {
+    type Output = Vec2D;
+    fn div(self, divisor: f64) -> Vec2D {
+        Vec2D {
+            x: self.x / divisor,
+            y: self.y / divisor,
+        }
+    }
+}
+
+impl Neg for Vec2D {
+    type Output = Vec2D;
+    fn neg(self) -> Vec2D {
+        Vec2D {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
 impl Vec2D {
     pub fn zero() -> Vec2D {
         Vec2D { x: 0.0, y: 0.0 }
@@ -60,6 +93,10 @@ impl Vec2D {
         self.dot(self).sqrt()
     }
 
+    pub fn angle(self) -> f64 {
+        self.y.atan2(self.x)
+    }
+
     pub fn cross(self, other: Vec2D) -> f64 {
         self.x * other.y - self.y * other.x
     }
@@ -84,8 +121,181 @@ impl Vec2D {
         }
     }
 
+    /// Rotated a quarter turn, from the x axis towards the y axis.
+    pub fn perp(self) -> Vec2D {
+        Vec2D { x: -self.y, y: self.x }
+    }
+
+    /// `self` at 0, `other` at 1.
+    pub fn lerp(self, other: Vec2D, t: f64) -> Vec2D {
+        self + (other - self) * t
+    }
+
     pub fn clip(&mut self, other: &Vec2D) {
         self.x = clip(self.x, other.x);
         self.y = clip(self.y, other.y);
     }
+
+    /// `self - other` on a field of `size` that wraps around,
+    /// the shortest way, which can go across the edges.
+    pub fn wrapped_sub(self, other: Vec2D, size: Vec2D) -> Vec2D {
+        let wrap = |d: f64, size: f64| d - size * (d / size).round();
+        let d = self - other;
+        Vec2D { x: wrap(d.x, size.x), y: wrap(d.y, size.y) }
+    }
+}
+
+/*
+ *     An affine transform: points are mapped to `x_axis * p.x + y_axis * p.y
+ * + translation`. They're built up in the order they're applied, e.g. a model
+ * scaled, then rotated, then moved into place:
+ *
+ *     Transform2D::scale(2.0).rotated(angle).translated(pos)
+ *
+ * which is what `Transform2D::new(pos, angle, 2.0)` makes.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2D {
+    pub x_axis: Vec2D,
+    pub y_axis: Vec2D,
+    pub translation: Vec2D,
+}
+
+impl Transform2D {
+    pub fn identity() -> Transform2D {
+        Transform2D {
+            x_axis: Vec2D { x: 1.0, y: 0.0 },
+            y_axis: Vec2D { x: 0.0, y: 1.0 },
+            translation: Vec2D::zero(),
+        }
+    }
+
+    pub fn translate(offset: Vec2D) -> Transform2D {
+        Transform2D { translation: offset, ..Transform2D::identity() }
+    }
+
+    pub fn rotate(angle: f64) -> Transform2D {
+        let x_axis = Vec2D::one().rotate(angle);
+        Transform2D { x_axis: x_axis, y_axis: x_axis.perp(), translation: Vec2D::zero() }
+    }
+
+    pub fn scale(factor: f64) -> Transform2D {
+        Transform2D {
+            x_axis: Vec2D { x: factor, y: 0.0 },
+            y_axis: Vec2D { x: 0.0, y: factor },
+            translation: Vec2D::zero(),
+        }
+    }
+
+    /// Scaled by `scale`, rotated by `angle`, and then moved to `pos`.
+    pub fn new(pos: Vec2D, angle: f64, scale: f64) -> Transform2D {
+        Transform2D::scale(scale).rotated(angle).translated(pos)
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform2D) -> Transform2D {
+        Transform2D {
+            x_axis: next.apply_vector(self.x_axis),
+            y_axis: next.apply_vector(self.y_axis),
+            translation: next.apply(self.translation),
+        }
+    }
+
+    pub fn translated(&self, offset: Vec2D) -> Transform2D {
+        self.then(&Transform2D::translate(offset))
+    }
+
+    pub fn rotated(&self, angle: f64) -> Transform2D {
+        self.then(&Transform2D::rotate(angle))
+    }
+
+    pub fn scaled(&self, factor: f64) -> Transform2D {
+        self.then(&Transform2D::scale(factor))
+    }
+
+    /// `None` if it flattens the plane, e.g. when scaling by 0.
+    pub fn invert(&self) -> Option<Transform2D> {
+        let det = self.x_axis.cross(self.y_axis);
+        if det == 0.0 {
+            return None;
+        }
+        let linear = Transform2D {
+            x_axis: Vec2D { x: self.y_axis.y, y: -self.x_axis.y } / det,
+            y_axis: Vec2D { x: -self.y_axis.x, y: self.x_axis.x } / det,
+            translation: Vec2D::zero(),
+        };
+        Some(Transform2D { translation: -linear.apply_vector(self.translation), ..linear })
+    }
+
+    pub fn apply(&self, p: Vec2D) -> Vec2D {
+        self.apply_vector(p) + self.translation
+    }
+
+    /// Like `apply`, without the translation, for directions and speeds.
+    pub fn apply_vector(&self, v: Vec2D) -> Vec2D {
+        self.x_axis * v.x + self.y_axis * v.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec2D, b: Vec2D) -> bool {
+        (a - b).len() < 1e-9
+    }
+
+    const POINTS: &[Vec2D] = &[
+        Vec2D { x: 0.0, y: 0.0 },
+        Vec2D { x: 3.0, y: -4.0 },
+        Vec2D { x: -12.5, y: 7.25 },
+    ];
+
+    #[test]
+    fn vector_identities() {
+        let v = Vec2D { x: 3.0, y: -4.0 };
+        let w = Vec2D { x: -1.5, y: 2.0 };
+        assert_eq!(v * 2.0, v.scale(2.0));
+        assert_eq!(v * 2.0 / 2.0, v);
+        assert_eq!(-(-v), v);
+        assert_eq!(v + -v, Vec2D::zero());
+        assert_eq!(v.perp().dot(v), 0.0);
+        assert_eq!(v.cross(v.perp()), v.dot(v));
+        assert!(close(v.perp(), v.rotate(::std::f64::consts::PI / 2.0)));
+        assert_eq!(v.lerp(w, 0.0), v);
+        assert_eq!(v.lerp(w, 1.0), w);
+        assert_eq!(v.lerp(w, 0.5), (v + w) / 2.0);
+    }
+
+    #[test]
+    fn wrapped_sub_takes_the_short_way() {
+        let size = Vec2D { x: 100.0, y: 50.0 };
+        let a = Vec2D { x: 5.0, y: 45.0 };
+        let b = Vec2D { x: 95.0, y: 5.0 };
+        assert!(close(a.wrapped_sub(b, size), Vec2D { x: 10.0, y: -10.0 }));
+        assert!(close(b.wrapped_sub(a, size), -a.wrapped_sub(b, size)));
+        let c = Vec2D { x: 40.0, y: 30.0 };
+        assert!(close(c.wrapped_sub(a, size), c - a));
+    }
+
+    #[test]
+    fn transform_identities() {
+        let t = Transform2D::new(Vec2D { x: 10.0, y: -3.0 }, 0.7, 2.5);
+        let u = Transform2D::rotate(-1.9).scaled(0.5).translated(Vec2D { x: 1.0, y: 1.0 });
+        let v = Transform2D::translate(Vec2D { x: -4.0, y: 2.0 }).rotated(3.0);
+        let identity = Transform2D::identity();
+        let inverse = t.invert().unwrap();
+        for &p in POINTS {
+            assert_eq!(identity.apply(p), p);
+            assert!(close(t.apply(p), p.scale(2.5).rotate(0.7) + Vec2D { x: 10.0, y: -3.0 }));
+            assert!(close(t.then(&identity).apply(p), t.apply(p)));
+            assert!(close(identity.then(&t).apply(p), t.apply(p)));
+            assert!(close(t.then(&u).apply(p), u.apply(t.apply(p))));
+            assert!(close(t.then(&u).then(&v).apply(p), t.then(&u.then(&v)).apply(p)));
+            assert!(close(inverse.apply(t.apply(p)), p));
+            assert!(close(t.apply(inverse.apply(p)), p));
+            assert!(close(t.apply_vector(p), t.apply(p) - t.apply(Vec2D::zero())));
+        }
+        assert_eq!(Transform2D::scale(0.0).invert(), None);
+    }
 }
@@ -0,0 +1,318 @@
+use std::cell::{RefCell};
+use std::collections::{HashMap};
+use std::fs::{File};
+use std::io::{Read};
+use std::rc::{Rc};
+use std::time::{Instant};
+use crate::eventloop::{self, Event, TimerHandle, EVENTS_PAGE};
+use crate::host::{Host};
+use crate::rng::{RNGSourceError};
+
+/// A layer's `<path>` element.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgLayer {
+    pub path: String,
+    pub stroke: String,
+    pub width: f64,
+    pub opacity: f64,
+}
+
+/// What the SVG element would show.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgSink {
+    // by `Style`, as many as have been set
+    pub layers: Vec<SvgLayer>,
+    pub background: String,
+    pub transform: String,
+    pub clip: (f64, f64, f64, f64),
+}
+
+impl SvgSink {
+    fn layer_mut(&mut self, layer: u32) -> &mut SvgLayer {
+        let idx = layer as usize;
+        if self.layers.len() <= idx {
+            self.layers.resize(idx + 1, SvgLayer::default());
+        }
+        &mut self.layers[idx]
+    }
+
+    /// Whether any layer has something drawn.
+    pub fn is_drawn(&self) -> bool {
+        self.layers.iter().any(|layer| !layer.path.is_empty())
+    }
+}
+
+struct NativeLoop {
+    id: u32,
+    wants_frame: bool,
+    subscriptions: u32,
+}
+
+struct NativeTimer {
+    loop_id: u32,
+    handle: u32,
+    due: f64,
+    interval: Option<f64>,
+}
+
+#[derive(Default)]
+struct NativeState {
+    svg: SvgSink,
+    display_list: Vec<u8>,
+    storage: HashMap<String, String>,
+    loops: Vec<NativeLoop>,
+    loop_counter: u32,
+    // loops shut down, that haven't got their `Destroyed` yet
+    destroyed: Vec<u32>,
+    timers: Vec<NativeTimer>,
+    timer_counter: u32,
+    // the time of the last `advance`
+    time: f64,
+}
+
+/*
+ *     A host for running the game outside of a browser: the clock is the
+ * system clock, randomness comes from the OS, text goes to stdout, and the
+ * SVG drawing and the storage are kept in memory.
+ *
+ *     Nothing happens on its own, like in a browser that never gets around
+ * to running its event loop. `advance` plays the part of the browser: it
+ * delivers `Destroyed` to loops that were shut down, fires due timers and
+ * sends animation frames, all at the time it's given.
+ */
+pub struct NativeHost {
+    start: Instant,
+    state: RefCell<NativeState>,
+}
+
+impl NativeHost {
+    pub fn new() -> NativeHost {
+        NativeHost {
+            start: Instant::now(),
+            state: RefCell::new(NativeState::default()),
+        }
+    }
+
+    pub fn svg(&self) -> SvgSink {
+        self.state.borrow().svg.clone()
+    }
+
+    /// The last frame drawn as a display list.
+    pub fn display_list(&self) -> Vec<u8> {
+        self.state.borrow().display_list.clone()
+    }
+
+    /// Categories of events a loop is subscribed to, `None` if it's not alive.
+    pub fn subscriptions(&self, id: u32) -> Option<u32> {
+        self.state.borrow().loops.iter()
+            .find(|l| l.id == id)
+            .map(|l| l.subscriptions)
+    }
+
+    /*
+     *     Events are delivered without the state borrowed, since handlers
+     * call back into the host, e.g. to request the next frame.
+     */
+    pub fn deliver_destroyed(&self) {
+        let destroyed = ::std::mem::take(&mut self.state.borrow_mut().destroyed);
+        for id in destroyed {
+            eventloop::deliver(id, Event::Destroyed);
+        }
+    }
+
+    fn next_due_timer(&self, time: f64) -> Option<(u32, u32, f64)> {
+        let mut state = self.state.borrow_mut();
+        let pos = state.timers.iter().enumerate()
+            .filter(|&(_, timer)| timer.due <= time)
+            .min_by(|a, b| a.1.due.total_cmp(&b.1.due))
+            .map(|(pos, _)| pos)?;
+        let timer = &mut state.timers[pos];
+        let fired = (timer.loop_id, timer.handle, timer.due);
+        match timer.interval {
+            // an interval shorter than a millisecond would never let time pass
+            Some(interval) => timer.due += interval.max(1.0),
+            None => { state.timers.remove(pos); },
+        }
+        Some(fired)
+    }
+
+    /// Shows or hides the page, for loops subscribed to `EVENTS_PAGE`.
+    pub fn set_visible(&self, visible: bool) {
+        let ids: Vec<u32> = self.state.borrow().loops.iter()
+            .filter(|l| l.subscriptions & EVENTS_PAGE != 0)
+            .map(|l| l.id)
+            .collect();
+        for id in ids {
+            eventloop::deliver(id, Event::VisibilityChange { visible: visible });
+        }
+    }
+
+    /// Fires the timers due by `time` without animation frames, like a
+    /// browser does for a hidden page or when frames stall.
+    pub fn advance_timers(&self, time: f64) {
+        self.deliver_destroyed();
+        while let Some((id, handle, due)) = self.next_due_timer(time) {
+            self.state.borrow_mut().time = due;
+            eventloop::deliver(id, Event::Timer { handle: TimerHandle::from_u32(handle), time: due });
+        }
+    }
+
+    /// Runs everything that would have happened by `time`, ending with an animation frame.
+    pub fn advance(&self, time: f64) {
+        self.advance_timers(time);
+        let frames: Vec<u32> = {
+            let mut state = self.state.borrow_mut();
+            state.time = time;
+            state.loops.iter_mut()
+                .filter(|l| l.wants_frame)
+                .map(|l| { l.wants_frame = false; l.id })
+                .collect()
+        };
+        for id in frames {
+            eventloop::deliver(id, Event::AnimationFrame { time: time });
+        }
+    }
+}
+
+thread_local! {
+    static DEFAULT_HOST: Rc<NativeHost> = Rc::new(NativeHost::new());
+}
+
+/// The host used natively, unless it's replaced with `set_host`.
+pub fn default_host() -> Rc<NativeHost> {
+    DEFAULT_HOST.with(|host| host.clone())
+}
+
+impl Default for NativeHost {
+    fn default() -> NativeHost {
+        NativeHost::new()
+    }
+}
+
+impl Host for NativeHost {
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1e3
+    }
+
+    fn fill_random(&self, buf: &mut [u8]) -> Result<(), RNGSourceError> {
+        File::open("/dev/urandom")
+            .and_then(|mut urandom| urandom.read_exact(buf))
+            .map_err(|_| RNGSourceError::UnknownError)
+    }
+
+    fn puts(&self, s: &str) {
+        println!("{}", s);
+    }
+
+    fn alert(&self, n: f64) {
+        println!("alert: {}", n);
+    }
+
+    fn svg_set_path(&self, layer: u32, path: &str) {
+        self.state.borrow_mut().svg.layer_mut(layer).path = path.to_string();
+    }
+
+    fn svg_set_style(&self, layer: u32, stroke: &str, width: f64, opacity: f64) {
+        let mut state = self.state.borrow_mut();
+        let layer = state.svg.layer_mut(layer);
+        layer.stroke = stroke.to_string();
+        layer.width = width;
+        layer.opacity = opacity;
+    }
+
+    fn svg_set_background(&self, color: &str) {
+        self.state.borrow_mut().svg.background = color.to_string();
+    }
+
+    fn svg_set_transform(&self, transform: &str) {
+        self.state.borrow_mut().svg.transform = transform.to_string();
+    }
+
+    fn svg_set_clip(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.state.borrow_mut().svg.clip = (x, y, width, height);
+    }
+
+    fn draw_display_list(&self, list: &[u8]) {
+        self.state.borrow_mut().display_list = list.to_vec();
+    }
+
+    fn storage_get(&self, key: &str) -> Option<String> {
+        self.state.borrow().storage.get(key).cloned()
+    }
+
+    fn storage_set(&self, key: &str, value: &str) {
+        self.state.borrow_mut().storage.insert(key.to_string(), value.to_string());
+    }
+
+    fn gamepad_rumble(&self, _strong: f64, _weak: f64, _duration_ms: f64) {}
+
+    fn event_loop_new(&self) -> u32 {
+        let mut state = self.state.borrow_mut();
+        state.loop_counter += 1;
+        let id = state.loop_counter;
+        state.loops.push(NativeLoop { id: id, wants_frame: false, subscriptions: 0 });
+        id
+    }
+
+    fn event_loop_raf(&self, id: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(l) = state.loops.iter_mut().find(|l| l.id == id) {
+            l.wants_frame = true;
+        }
+    }
+
+    fn event_loop_shutdown(&self, id: u32) -> bool {
+        let mut state = self.state.borrow_mut();
+        let pos = match state.loops.iter().position(|l| l.id == id) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        state.loops.remove(pos);
+        state.timers.retain(|timer| timer.loop_id != id);
+        state.destroyed.push(id);
+        true
+    }
+
+    fn event_loop_set_timer(&self, id: u32, delay_ms: f64, repeat: bool) -> u32 {
+        let mut state = self.state.borrow_mut();
+        if !state.loops.iter().any(|l| l.id == id) {
+            return 0;
+        }
+        state.timer_counter += 1;
+        let handle = state.timer_counter;
+        let due = state.time + delay_ms;
+        state.timers.push(NativeTimer {
+            loop_id: id,
+            handle: handle,
+            due: due,
+            interval: if repeat { Some(delay_ms) } else { None },
+        });
+        handle
+    }
+
+    fn event_loop_clear_timer(&self, id: u32, handle: u32) -> bool {
+        let mut state = self.state.borrow_mut();
+        let before = state.timers.len();
+        state.timers.retain(|timer| timer.loop_id != id || timer.handle != handle);
+        state.timers.len() != before
+    }
+
+    fn event_loop_subscribe(&self, id: u32, categories: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(l) = state.loops.iter_mut().find(|l| l.id == id) {
+            l.subscriptions |= categories;
+        }
+    }
+
+    fn event_loop_unsubscribe(&self, id: u32, categories: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(l) = state.loops.iter_mut().find(|l| l.id == id) {
+            l.subscriptions &= !categories;
+        }
+    }
+
+    // there are no elements, every selector matches
+    fn event_loop_set_target(&self, id: u32, _selector: &str) -> bool {
+        self.state.borrow().loops.iter().any(|l| l.id == id)
+    }
+}
@@ -0,0 +1,274 @@
+use crate::math::{Vec2D, Transform2D};
+
+/*
+ *     The simulation does its math through a `MathBackend`, so that it can
+ * be made to give the exact same results everywhere, for replays and
+ * checksums that are compared between the browser and native builds.
+ *
+ *     IEEE 754 requires `+`, `-`, `*`, `/` and `sqrt` to be correctly
+ * rounded, so they're the same on every target, and Rust never fuses them.
+ * `sin`, `cos` and `atan2` come from whatever libm the target has though,
+ * and those differ in the last bits. `SoftMath` implements them with only
+ * the basic operations, after fdlibm, which is what the rest of this file
+ * is about.
+ */
+pub trait MathBackend {
+    fn sin(x: f64) -> f64;
+    fn cos(x: f64) -> f64;
+    fn atan2(y: f64, x: f64) -> f64;
+
+    fn unit(angle: f64) -> Vec2D {
+        Vec2D { x: Self::cos(angle), y: Self::sin(angle) }
+    }
+
+    /// Same as `Vec2D::rotate`.
+    fn rotate(v: Vec2D, angle: f64) -> Vec2D {
+        let sin = Self::sin(angle);
+        let cos = Self::cos(angle);
+        Vec2D {
+            x: v.x * cos - v.y * sin,
+            y: v.y * cos + v.x * sin,
+        }
+    }
+
+    fn angle(v: Vec2D) -> f64 {
+        Self::atan2(v.y, v.x)
+    }
+
+    fn len(v: Vec2D) -> f64 {
+        v.dot(v).sqrt()
+    }
+
+    /// A position wrapped into a field of `size`.
+    fn wrap(mut pos: Vec2D, size: Vec2D) -> Vec2D {
+        pos.clip(&size);
+        pos
+    }
+
+    /// Same as `Transform2D::new`.
+    fn transform(pos: Vec2D, angle: f64, scale: f64) -> Transform2D {
+        let x_axis = Self::unit(angle) * scale;
+        Transform2D { x_axis: x_axis, y_axis: x_axis.perp(), translation: pos }
+    }
+}
+
+/// The target's own math, the fastest.
+pub struct StdMath;
+
+impl MathBackend for StdMath {
+    fn sin(x: f64) -> f64 {
+        x.sin()
+    }
+
+    fn cos(x: f64) -> f64 {
+        x.cos()
+    }
+
+    fn atan2(y: f64, x: f64) -> f64 {
+        y.atan2(x)
+    }
+}
+
+/// Bit-exact on every target with IEEE 754 doubles, wasm included.
+pub struct SoftMath;
+
+impl MathBackend for SoftMath {
+    fn sin(x: f64) -> f64 {
+        let (n, r) = reduce(x);
+        match n & 3 {
+            0 => kernel_sin(r),
+            1 => kernel_cos(r),
+            2 => -kernel_sin(r),
+            _ => -kernel_cos(r),
+        }
+    }
+
+    fn cos(x: f64) -> f64 {
+        let (n, r) = reduce(x);
+        match n & 3 {
+            0 => kernel_cos(r),
+            1 => -kernel_sin(r),
+            2 => -kernel_cos(r),
+            _ => kernel_sin(r),
+        }
+    }
+
+    fn atan2(y: f64, x: f64) -> f64 {
+        use std::f64::consts::{PI, FRAC_PI_2};
+        if x.is_nan() || y.is_nan() {
+            return f64::NAN;
+        }
+        if x > 0.0 {
+            atan(y / x)
+        } else if x < 0.0 {
+            if y >= 0.0 { atan(y / x) + PI } else { atan(y / x) - PI }
+        } else if y > 0.0 {
+            FRAC_PI_2
+        } else if y < 0.0 {
+            -FRAC_PI_2
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Which backend the simulation uses, see `MathBackend`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MathMode {
+    Native,
+    Deterministic,
+}
+
+/*
+ *     Reduces x to r in -PI/4..PI/4, x = n * PI/2 + r, with PI/2 split in
+ * three parts of 33 bits, so that n times a part is exact (Cody-Waite).
+ * That's accurate for the angles the game sees, it only gets inaccurate
+ * beyond 2^20 * PI/2, but stays deterministic.
+ */
+#[allow(clippy::excessive_precision)]
+fn reduce(x: f64) -> (i64, f64) {
+    use std::f64::consts::{FRAC_2_PI as INV_PIO2};
+    const PIO2_1: f64 = 1.57079632673412561417e+00;
+    const PIO2_2: f64 = 6.07710050630396597660e-11;
+    const PIO2_3: f64 = 2.02226624871116645580e-21;
+    let n = (x * INV_PIO2).round();
+    let r = ((x - n * PIO2_1) - n * PIO2_2) - n * PIO2_3;
+    (n as i64, r)
+}
+
+// fdlibm k_sin.c, sin(x) for x in -PI/4..PI/4, the constants as written there
+#[allow(clippy::excessive_precision)]
+fn kernel_sin(x: f64) -> f64 {
+    const S1: f64 = -1.66666666666666324348e-01;
+    const S2: f64 = 8.33333333332248946124e-03;
+    const S3: f64 = -1.98412698298579493134e-04;
+    const S4: f64 = 2.75573137070700676789e-06;
+    const S5: f64 = -2.50507602534068634195e-08;
+    const S6: f64 = 1.58969099521155010221e-10;
+    let z = x * x;
+    let v = z * x;
+    let r = S2 + z * (S3 + z * (S4 + z * (S5 + z * S6)));
+    x + v * (S1 + z * r)
+}
+
+// fdlibm k_cos.c, cos(x) for x in -PI/4..PI/4
+#[allow(clippy::excessive_precision)]
+fn kernel_cos(x: f64) -> f64 {
+    const C1: f64 = 4.16666666666666019037e-02;
+    const C2: f64 = -1.38888888888741095749e-03;
+    const C3: f64 = 2.48015872894767294178e-05;
+    const C4: f64 = -2.75573143513906633035e-07;
+    const C5: f64 = 2.08757232129817482790e-09;
+    const C6: f64 = -1.13596475577881948265e-11;
+    let z = x * x;
+    let r = z * (C1 + z * (C2 + z * (C3 + z * (C4 + z * (C5 + z * C6)))));
+    let hz = 0.5 * z;
+    let w = 1.0 - hz;
+    w + (((1.0 - w) - hz) + z * r)
+}
+
+// fdlibm s_atan.c
+#[allow(clippy::excessive_precision)]
+fn atan(x: f64) -> f64 {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+    const ATAN_HI: [f64; 4] = [
+        4.63647609000806093515e-01, // atan(0.5)hi
+        FRAC_PI_4,                  // atan(1.0)hi
+        9.82793723247329054082e-01, // atan(1.5)hi
+        FRAC_PI_2,                  // atan(inf)hi
+    ];
+    const ATAN_LO: [f64; 4] = [
+        2.26987774529616870924e-17,
+        3.06161699786838301793e-17,
+        1.39033110312309984516e-17,
+        6.12323399573676603587e-17,
+    ];
+    const AT: [f64; 11] = [
+        3.33333333333329318027e-01,
+        -1.99999999998764832476e-01,
+        1.42857142725034663711e-01,
+        -1.11111104054623557880e-01,
+        9.09088713343650656196e-02,
+        -7.69187620504482999495e-02,
+        6.66107313738753120669e-02,
+        -5.83357013379057348645e-02,
+        4.97687799461593236017e-02,
+        -3.65315727442169155270e-02,
+        1.62858201153657823623e-02,
+    ];
+    if x.is_nan() {
+        return x;
+    }
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x * sign;
+    // atan(x) = atan(c) + atan((x - c) / (1 + x*c)), for c near x
+    let (id, x) = if x < 0.4375 {
+        (None, x)
+    } else if x < 0.6875 {
+        (Some(0), (2.0 * x - 1.0) / (2.0 + x))
+    } else if x < 1.1875 {
+        (Some(1), (x - 1.0) / (x + 1.0))
+    } else if x < 2.4375 {
+        (Some(2), (x - 1.5) / (1.0 + 1.5 * x))
+    } else {
+        (Some(3), -1.0 / x)
+    };
+    let z = x * x;
+    let w = z * z;
+    // odd and even terms, separately
+    let s1 = z * (AT[0] + w * (AT[2] + w * (AT[4] + w * (AT[6] + w * (AT[8] + w * AT[10])))));
+    let s2 = w * (AT[1] + w * (AT[3] + w * (AT[5] + w * (AT[7] + w * AT[9]))));
+    match id {
+        None => sign * (x - x * (s1 + s2)),
+        Some(id) => sign * (ATAN_HI[id] - ((x * (s1 + s2) - ATAN_LO[id]) - x)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn angles() -> impl Iterator<Item = f64> {
+        (-20000..20000).map(|i| i as f64 * 0.0037)
+            .chain([0.0, -0.0, PI, -PI, PI / 2.0, PI / 4.0, 1e-300, 1e6].iter().cloned())
+    }
+
+    #[test]
+    fn soft_trig_is_accurate() {
+        for x in angles() {
+            assert!((SoftMath::sin(x) - x.sin()).abs() < 1e-15, "sin({})", x);
+            assert!((SoftMath::cos(x) - x.cos()).abs() < 1e-15, "cos({})", x);
+            let (y, x) = (x.sin() * 3.0, x.cos() * 0.5);
+            assert!((SoftMath::atan2(y, x) - y.atan2(x)).abs() < 1e-15, "atan2({}, {})", y, x);
+        }
+        assert!(SoftMath::sin(f64::NAN).is_nan());
+        assert_eq!(SoftMath::atan2(1e300, 1e-300), PI / 2.0);
+        assert_eq!(SoftMath::atan2(0.0, -1.0), PI);
+    }
+
+    /*
+     *     The bits are what every target computes, they only change if
+     * `SoftMath` does, and then replays recorded before don't play anymore.
+     */
+    #[test]
+    fn soft_trig_bits() {
+        let bits = [
+            SoftMath::sin(0.5).to_bits(),
+            SoftMath::cos(0.5).to_bits(),
+            SoftMath::sin(-1234.5678).to_bits(),
+            SoftMath::atan2(-0.3, -2.0).to_bits(),
+        ];
+        assert_eq!(bits, [0x3fdeaee8744b05f0, 0x3fec1528065b7d50, 0xbfb3fa00084ee3dd, 0xc007f10e1dc6b048]);
+    }
+
+    #[test]
+    fn backends_agree_on_transforms() {
+        let p = Vec2D { x: 3.0, y: -2.0 };
+        let pos = Vec2D { x: 10.0, y: 20.0 };
+        assert_eq!(StdMath::transform(pos, 0.3, 2.0).apply(p), Transform2D::new(pos, 0.3, 2.0).apply(p));
+        assert_eq!(StdMath::rotate(p, 0.3), p.rotate(0.3));
+        assert_eq!(StdMath::angle(p), p.angle());
+        assert!((SoftMath::rotate(p, 0.3) - p.rotate(0.3)).len() < 1e-12);
+    }
+}
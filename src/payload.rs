@@ -0,0 +1,133 @@
+/*
+ *     Events that don't fit into three numbers carry a payload: the host
+ * allocates a buffer with `alloc(len)`, writes the event data into it and
+ * passes the pointer and the length to `event_loop_cb`. From then on the
+ * buffer belongs to Rust, and is freed once the event is decoded, whether
+ * it's valid or not, so the host must not touch or free it afterwards.
+ * A null pointer is an empty payload.
+ *
+ *     The data is a sequence of fields, all little-endian:
+ *
+ * - `u32`, `i32`: 4 bytes, `f64`: 8 bytes;
+ * - strings and byte arrays: a `u32` byte length, then the bytes,
+ *   strings are UTF-8;
+ * - lists: a `u32` number of items, then the items.
+ *
+ * Each event documents its fields in eventloop.rs, and demo.js writes
+ * them with `PayloadWriter`.
+ */
+pub struct Payload {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Payload {
+    /// Takes ownership of a buffer the host allocated with `alloc(len)`.
+    ///
+    /// # Safety
+    ///
+    /// Unless `ptr` is null, it must have been returned by `alloc(len)` with
+    /// this same `len`, and all `len` bytes must have been written. The buffer
+    /// moves into the payload, nothing else may use or free it afterwards.
+    pub unsafe fn from_host(ptr: u32, len: u32) -> Payload {
+        let buf = if ptr == 0 {
+            Vec::new()
+        } else {
+            Vec::from_raw_parts(ptr as *mut u8, len as usize, len as usize)
+        };
+        Payload::from_bytes(buf)
+    }
+
+    pub fn from_bytes(buf: Vec<u8>) -> Payload {
+        Payload {
+            buf: buf,
+            pos: 0,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn array<A: Default + AsMut<[u8]>>(&mut self) -> Option<A> {
+        let mut array = A::default();
+        let len = array.as_mut().len();
+        array.as_mut().copy_from_slice(self.take(len)?);
+        Some(array)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub fn i32(&mut self) -> Option<i32> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    pub fn bytes(&mut self) -> Option<Vec<u8>> {
+        let len = self.u32()? as usize;
+        self.take(len).map(|bytes| bytes.to_vec())
+    }
+
+    /// Invalid UTF-8 is replaced, like in host strings.
+    pub fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        self.take(len).map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Reads a list of items with `item`.
+    pub fn list<T, F>(&mut self, mut item: F) -> Option<Vec<T>>
+        where F: FnMut(&mut Payload) -> Option<T>
+    {
+        let count = self.u32()? as usize;
+        // every item takes at least a byte, don't trust the count for allocating
+        let mut items = Vec::with_capacity(count.min(self.buf.len() - self.pos));
+        for _ in 0..count {
+            items.push(item(self)?);
+        }
+        Some(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_fields_in_order() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&7u32.to_le_bytes());
+        buf.extend_from_slice(&(-3i32).to_le_bytes());
+        buf.extend_from_slice(&0.5f64.to_le_bytes());
+        buf.extend_from_slice(&3u32.to_le_bytes());
+        buf.extend_from_slice("key".as_bytes());
+        buf.extend_from_slice(&2u32.to_le_bytes());
+        buf.extend_from_slice(&1.0f64.to_le_bytes());
+        buf.extend_from_slice(&2.0f64.to_le_bytes());
+        let mut payload = Payload::from_bytes(buf);
+        assert_eq!(payload.u32(), Some(7));
+        assert_eq!(payload.i32(), Some(-3));
+        assert_eq!(payload.f64(), Some(0.5));
+        assert_eq!(payload.string(), Some("key".to_string()));
+        assert_eq!(payload.list(Payload::f64), Some(vec![1.0, 2.0]));
+        assert_eq!(payload.u32(), None);
+    }
+
+    #[test]
+    fn truncated_payloads_are_rejected() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&1000u32.to_le_bytes());
+        buf.extend_from_slice("short".as_bytes());
+        assert_eq!(Payload::from_bytes(buf.clone()).string(), None);
+        assert_eq!(Payload::from_bytes(buf).list(Payload::u32), None);
+        assert_eq!(Payload::from_bytes(vec![1, 2]).u32(), None);
+        assert_eq!(unsafe { Payload::from_host(0, 0) }.bytes(), None);
+    }
+}
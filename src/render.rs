@@ -0,0 +1,355 @@
+use std::fmt::Write;
+use crate::math::{Vec2D};
+use crate::viewport::{Viewport};
+
+/*
+ *     What the game draws is lines, grouped by what they show, so that
+ * backends can style the groups differently, see `Theme`. `render_game`
+ * draws through a `Renderer`:
+ *
+ *     `SvgLayers` is the `d` attribute of an SVG `<path>` per layer.
+ *     `DisplayList` is a compact binary list of commands, which the host
+ *     reads out of wasm memory and draws, e.g. on a canvas.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Style {
+    Hud = 0,
+    Ship = 1,
+    Bullet = 2,
+    Asteroid = 3,
+    Explosion = 4,
+    // pause sign, banner, crosshair and touch controls
+    Overlay = 5,
+}
+
+pub const LAYER_COUNT: usize = Style::ALL.len();
+
+impl Style {
+    /// In drawing order, the index of each is its number.
+    pub const ALL: &[Style] = &[
+        Style::Hud, Style::Ship, Style::Bullet, Style::Asteroid, Style::Explosion, Style::Overlay,
+    ];
+
+    /// The page draws the layer with the element `layer-<name>`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Style::Hud => "hud",
+            Style::Ship => "ship",
+            Style::Bullet => "bullets",
+            Style::Asteroid => "asteroids",
+            Style::Explosion => "explosions",
+            Style::Overlay => "overlay",
+        }
+    }
+}
+
+/// Which backend draws the game in the page.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenderTarget {
+    SvgPath = 0,
+    DisplayList = 1,
+}
+
+impl RenderTarget {
+    pub fn from_u32(value: u32) -> Option<RenderTarget> {
+        match value {
+            0 => Some(RenderTarget::SvgPath),
+            1 => Some(RenderTarget::DisplayList),
+            _ => None,
+        }
+    }
+}
+
+pub trait Renderer {
+    /// Everything drawn until the next layer is drawn in `style`.
+    /// Layers can be begun again, what's drawn then is added to them.
+    fn begin_layer(&mut self, style: Style);
+
+    /// Joins the points with lines, it's closed if the last point is the first.
+    fn polyline(&mut self, points: &[Vec2D]);
+
+    fn line(&mut self, from: Vec2D, to: Vec2D) {
+        self.polyline(&[from, to]);
+    }
+
+    /// Draws `text` left to right from its top left corner at `pos`. Characters
+    /// are 2 by 3 times `scale` and 3 times `scale` apart. Only digits have
+    /// glyphs, other characters are blank.
+    fn text(&mut self, pos: Vec2D, scale: f64, text: &str) {
+        for (idx, c) in text.chars().enumerate() {
+            let glyph = match c.to_digit(10) {
+                Some(digit) => VECTOR_DIGITS[digit as usize],
+                None => continue,
+            };
+            let origin = pos + Vec2D { x: (idx as f64) * 3.0 * scale, y: 0.0 };
+            let points: Vec<Vec2D> = glyph.iter().map(|&p| origin + p * scale).collect();
+            self.polyline(&points);
+        }
+    }
+}
+
+/// The `d` attribute of an SVG `<path>`, all layers look the same.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgPath {
+    pub d: String,
+}
+
+impl SvgPath {
+    pub fn new() -> SvgPath {
+        SvgPath { d: String::new() }
+    }
+}
+
+impl Renderer for SvgPath {
+    fn begin_layer(&mut self, _style: Style) {}
+
+    fn polyline(&mut self, points: &[Vec2D]) {
+        for (i, p) in points.iter().enumerate() {
+            let c = if i == 0 { 'M' } else { 'L' };
+            write!(self.d, "{}{:.2} {:.2} ", c, p.x, p.y)
+                .expect("could not write string?");
+        }
+    }
+}
+
+/// A path for each layer, so that each can have its own style.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgLayers {
+    pub paths: [SvgPath; LAYER_COUNT],
+    current: usize,
+}
+
+impl SvgLayers {
+    pub fn new() -> SvgLayers {
+        SvgLayers::default()
+    }
+
+    pub fn path(&self, style: Style) -> &str {
+        &self.paths[style as usize].d
+    }
+}
+
+impl Renderer for SvgLayers {
+    fn begin_layer(&mut self, style: Style) {
+        self.current = style as usize;
+    }
+
+    fn polyline(&mut self, points: &[Vec2D]) {
+        self.paths[self.current].polyline(points);
+    }
+}
+
+/*
+ *     Display list commands, each an opcode byte and its arguments, numbers
+ * are little-endian and unaligned, coordinates are f32:
+ *
+ *     VIEWPORT  translate x, y, scale, then the clip rect x, y, width, height,
+ *               coordinates are in the scene, see `Viewport`
+ *     LAYER     u8 `Style`
+ *     POLYLINE  u32 count, then count points x, y
+ *
+ * Text is drawn as polylines, so the host doesn't need a font.
+ */
+pub const DISPLAY_LIST_VIEWPORT: u8 = 1;
+pub const DISPLAY_LIST_LAYER: u8 = 2;
+pub const DISPLAY_LIST_POLYLINE: u8 = 3;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplayList {
+    pub bytes: Vec<u8>,
+}
+
+impl DisplayList {
+    pub fn new() -> DisplayList {
+        DisplayList { bytes: Vec::new() }
+    }
+
+    /// Keeps the memory, so that frames don't allocate once it's big enough.
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    fn f32(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&(value as f32).to_le_bytes());
+    }
+
+    pub fn viewport(&mut self, viewport: &Viewport) {
+        let (origin, size) = viewport.scene_rect();
+        self.bytes.push(DISPLAY_LIST_VIEWPORT);
+        for &value in [viewport.offset.x, viewport.offset.y, viewport.scale,
+                       origin.x, origin.y, size.x, size.y].iter() {
+            self.f32(value);
+        }
+    }
+}
+
+impl Renderer for DisplayList {
+    fn begin_layer(&mut self, style: Style) {
+        self.bytes.push(DISPLAY_LIST_LAYER);
+        self.bytes.push(style as u8);
+    }
+
+    fn polyline(&mut self, points: &[Vec2D]) {
+        if points.is_empty() {
+            return;
+        }
+        self.bytes.push(DISPLAY_LIST_POLYLINE);
+        self.bytes.extend_from_slice(&(points.len() as u32).to_le_bytes());
+        for p in points {
+            self.f32(p.x);
+            self.f32(p.y);
+        }
+    }
+}
+
+const VECTOR_DIGITS: &[&[Vec2D]] = &[
+    // 0
+    &[
+        Vec2D { x: 1.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 1.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+        Vec2D { x: 0.0, y: 2.0 },
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 1.0, y: 0.0 },
+    ],
+    // 1
+    &[
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 1.0, y: 0.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+    ],
+    // 2
+    &[
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 1.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 1.0 },
+        Vec2D { x: 0.0, y: 3.0 },
+        Vec2D { x: 2.0, y: 3.0 },
+    ],
+    // 3
+    &[
+        Vec2D { x: 0.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 0.0 },
+        Vec2D { x: 1.0, y: 1.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+        Vec2D { x: 0.0, y: 3.0 },
+    ],
+    // 4
+    &[
+        Vec2D { x: 1.0, y: 0.0 },
+        Vec2D { x: 0.0, y: 2.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 2.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 3.0 },
+    ],
+    // 5
+    &[
+        Vec2D { x: 2.0, y: 0.0 },
+        Vec2D { x: 0.0, y: 0.0 },
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 1.0, y: 1.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+        Vec2D { x: 0.0, y: 3.0 },
+    ],
+    // 6
+    &[
+        Vec2D { x: 2.0, y: 0.0 },
+        Vec2D { x: 1.0, y: 0.0 },
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 0.0, y: 2.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 1.0, y: 1.0 },
+        Vec2D { x: 0.0, y: 1.0 },
+    ],
+    // 7
+    &[
+        Vec2D { x: 0.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 0.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+    ],
+    // 8
+    &[
+        Vec2D { x: 0.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 0.0 },
+        Vec2D { x: 2.0, y: 1.0 },
+        Vec2D { x: 0.0, y: 2.0 },
+        Vec2D { x: 0.0, y: 3.0 },
+        Vec2D { x: 2.0, y: 3.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 0.0, y: 0.0 },
+    ],
+    // 9
+    &[
+        Vec2D { x: 0.0, y: 3.0 },
+        Vec2D { x: 1.0, y: 3.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+        Vec2D { x: 2.0, y: 1.0 },
+        Vec2D { x: 1.0, y: 0.0 },
+        Vec2D { x: 0.0, y: 1.0 },
+        Vec2D { x: 1.0, y: 2.0 },
+        Vec2D { x: 2.0, y: 2.0 },
+    ]
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svg_path_joins_points() {
+        let mut path = SvgPath::new();
+        path.begin_layer(Style::Ship);
+        path.line(Vec2D { x: 1.0, y: 2.0 }, Vec2D { x: 3.5, y: -4.0 });
+        path.text(Vec2D { x: 10.0, y: 0.0 }, 1.0, " 1");
+        assert_eq!(path.d, "M1.00 2.00 L3.50 -4.00 M13.00 1.00 L14.00 0.00 L14.00 3.00 ");
+    }
+
+    #[test]
+    fn svg_layers_split_paths() {
+        let mut layers = SvgLayers::new();
+        layers.begin_layer(Style::Asteroid);
+        layers.line(Vec2D { x: 0.0, y: 0.0 }, Vec2D { x: 1.0, y: 0.0 });
+        layers.begin_layer(Style::Hud);
+        layers.line(Vec2D { x: 2.0, y: 0.0 }, Vec2D { x: 3.0, y: 0.0 });
+        layers.begin_layer(Style::Asteroid);
+        layers.line(Vec2D { x: 4.0, y: 0.0 }, Vec2D { x: 5.0, y: 0.0 });
+        assert_eq!(layers.path(Style::Asteroid), "M0.00 0.00 L1.00 0.00 M4.00 0.00 L5.00 0.00 ");
+        assert_eq!(layers.path(Style::Hud), "M2.00 0.00 L3.00 0.00 ");
+        assert_eq!(layers.path(Style::Ship), "");
+        for (idx, style) in Style::ALL.iter().enumerate() {
+            assert_eq!(*style as usize, idx);
+        }
+    }
+
+    #[test]
+    fn demo_page_has_every_layer() {
+        let page = include_str!("../demo/demo.html");
+        for style in Style::ALL.iter() {
+            let element = format!("<path id=\"layer-{}\">", style.name());
+            assert!(page.contains(&element), "demo.html has no {}", element);
+        }
+    }
+
+    #[test]
+    fn display_list_encodes_commands() {
+        let mut list = DisplayList::new();
+        list.begin_layer(Style::Bullet);
+        list.polyline(&[]);
+        list.line(Vec2D { x: 1.0, y: 2.0 }, Vec2D { x: -0.5, y: 0.0 });
+        let mut expected = vec![DISPLAY_LIST_LAYER, Style::Bullet as u8, DISPLAY_LIST_POLYLINE, 2, 0, 0, 0];
+        for &value in [1.0f32, 2.0, -0.5, 0.0].iter() {
+            expected.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(list.bytes, expected);
+
+        list.clear();
+        list.viewport(&Viewport::new(Vec2D { x: 1280.0, y: 720.0 }));
+        assert_eq!(list.bytes[0], DISPLAY_LIST_VIEWPORT);
+        assert_eq!(list.bytes.len(), 1 + 7 * 4);
+    }
+}
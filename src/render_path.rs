@@ -1,6 +1,9 @@
-use ::game::{Game, Bullet, Asteroid, Explosion, InputIndex};
-use ::math::{Vec2D};
-use std::fmt::Write;
+use crate::game::{Game, GameState, Bullet, Asteroid, Explosion, ControlScheme, AxisIndex, MathMode};
+use crate::math::{Vec2D, Transform2D};
+use crate::numeric::{MathBackend, StdMath, SoftMath};
+use crate::render::{Renderer, Style};
+use crate::touch::{TouchControls, STICK_RADIUS, FIRE_RADIUS};
+use crate::viewport::{Viewport};
 
 const SHIP_POINTS: &[Vec2D] = &[
     Vec2D { x: 10.0, y: 0.0 },
@@ -17,64 +20,90 @@ const FLARE: &[Vec2D] = &[
     Vec2D { x: -8.0, y: -1.5 },
 ];
 
-fn render_ship(buf: &mut String, game: &Game) {
+fn transformed(points: &[Vec2D], model: &Transform2D) -> Vec<Vec2D> {
+    points.iter().map(|&p| model.apply(p)).collect()
+}
+
+fn render_ship<M: MathBackend>(r: &mut dyn Renderer, game: &Game) {
     let ship = &game.ship;
     if ship.dead { return; }
     let inputs = &game.inputs;
-    for (i, p) in SHIP_POINTS.iter().enumerate() {
-        let c = if i == 0 { 'M' } else { 'L' };
-        let p_c = p.scale(2.0).rotate(ship.angle) + ship.pos;
-        write!(buf, "{}{:.2} {:.2} ", c, p_c.x, p_c.y)
-            .expect("could not write string?");
-    }
-    if inputs.is_down(InputIndex::Forward) || inputs.is_down(InputIndex::Backward) {
-        for (i, p) in FLARE.iter().enumerate() {
-            let c = if i == 0 { 'M' } else { 'L' };
-            let p_c = p.scale(2.0).rotate(ship.angle) + ship.pos;
-            write!(buf, "{}{:.2} {:.2} ", c, p_c.x, p_c.y)
-                .expect("could not write string?");
-        }
+    let model = M::transform(ship.pos, ship.angle, 2.0);
+    r.polyline(&transformed(SHIP_POINTS, &model));
+    if inputs.axis(AxisIndex::Thrust) != 0.0 {
+        r.polyline(&transformed(FLARE, &model));
     }
 }
 
-fn render_bullet(buf: &mut String, bullet: &Bullet) {
-    let offset = Vec2D::zero();
-    let start = bullet.pos + offset;
-    let end = bullet.pos + bullet.speed.normalize().scale(5.0);
-    write!(buf, "M{:.2} {:.2} L{:.2} {:.2} ", start.x, start.y, end.x, end.y)
-        .expect("could not write string?");
+fn render_bullet(r: &mut dyn Renderer, bullet: &Bullet) {
+    let end = bullet.pos + bullet.speed.normalize() * 5.0;
+    r.line(bullet.pos, end);
 }
 
-fn render_asteroid(buf: &mut String, asteroid: &Asteroid) {
-    let offset = Vec2D::zero();
-    let mid = asteroid.pos + offset;
+fn render_asteroid<M: MathBackend>(r: &mut dyn Renderer, asteroid: &Asteroid) {
+    let model = M::transform(asteroid.pos, asteroid.angle, asteroid.size);
     let cnt = asteroid.style;
     let angle = ::std::f64::consts::PI * 2.0 / (cnt as f64);
-    let one = Vec2D::one().scale(asteroid.size).rotate(asteroid.angle);
-    for i in 0..(cnt+1) {
-        let c = if i == 0 { 'M' } else { 'L' };
-        let p = mid + one.rotate(angle * (i as f64));
-        write!(buf, "{}{:.2} {:.2}", c, p.x, p.y)
-            .expect("could not write string?");
+    let points: Vec<Vec2D> = (0..(cnt+1))
+        .map(|i| model.apply(M::unit(angle * (i as f64))))
+        .collect();
+    r.polyline(&points);
+}
+
+fn render_crosshair(r: &mut dyn Renderer, game: &Game) {
+    const CROSSHAIR_SIZE: f64 = 8.0;
+    if game.config.control_scheme != ControlScheme::MouseAim { return; }
+    if let Some(p) = game.inputs.aim() {
+        r.line(Vec2D { x: p.x - CROSSHAIR_SIZE, y: p.y }, Vec2D { x: p.x + CROSSHAIR_SIZE, y: p.y });
+        r.line(Vec2D { x: p.x, y: p.y - CROSSHAIR_SIZE }, Vec2D { x: p.x, y: p.y + CROSSHAIR_SIZE });
+    }
+}
+
+fn render_circle<M: MathBackend>(r: &mut dyn Renderer, center: Vec2D, radius: f64) {
+    const CIRCLE_SEGMENTS: usize = 24;
+    let angle = ::std::f64::consts::PI * 2.0 / (CIRCLE_SEGMENTS as f64);
+    let model = Transform2D::scale(radius).translated(center);
+    let points: Vec<Vec2D> = (0..(CIRCLE_SEGMENTS+1))
+        .map(|i| model.apply(M::unit(angle * (i as f64))))
+        .collect();
+    r.polyline(&points);
+}
+
+fn render_touch_controls<M: MathBackend>(r: &mut dyn Renderer, touch: &TouchControls) {
+    const KNOB_RADIUS: f64 = 40.0;
+    const FIRE_PRESSED_RADIUS: f64 = 70.0;
+    if !touch.active { return; }
+    let stick_center = touch.stick_center();
+    render_circle::<M>(r, stick_center, STICK_RADIUS);
+    render_circle::<M>(r, stick_center + touch.stick() * STICK_RADIUS, KNOB_RADIUS);
+    let fire_center = touch.fire_center();
+    render_circle::<M>(r, fire_center, FIRE_RADIUS);
+    if touch.fire_held() {
+        render_circle::<M>(r, fire_center, FIRE_PRESSED_RADIUS);
+    }
+}
+
+fn render_pause(r: &mut dyn Renderer, game: &Game) {
+    if game.game_state != GameState::Paused { return; }
+    let center = game.config.field_size / 2.0;
+    for &x in [-20.0, 20.0].iter() {
+        r.line(Vec2D { x: center.x + x, y: center.y - 40.0 }, Vec2D { x: center.x + x, y: center.y + 40.0 });
     }
 }
 
-fn render_lives(buf: &mut String, lives: u64) {
+fn render_lives<M: MathBackend>(r: &mut dyn Renderer, lives: u64, viewport: &Viewport) {
     const LIFE_STEP: f64 = 40.0;
     const UP_ANGLE: f64 = ::std::f64::consts::PI * -0.5;
+    let hud_scale = viewport.hud_scale();
     for l in 0..lives {
-        let y = -50.0;
-        let x = ((l + 1) as f64) * LIFE_STEP;
-        for (i, p) in SHIP_POINTS.iter().enumerate() {
-            let c = if i == 0 { 'M' } else { 'L' };
-            let p_c = p.scale(2.0).rotate(UP_ANGLE) + Vec2D { x, y };
-            write!(buf, "{}{:.2} {:.2} ", c, p_c.x, p_c.y)
-                .expect("could not write string?");
-        }
+        let y = -viewport.hud_height() * 0.5;
+        let x = ((l + 1) as f64) * LIFE_STEP * hud_scale;
+        let model = M::transform(Vec2D { x, y }, UP_ANGLE, 2.0 * hud_scale);
+        r.polyline(&transformed(SHIP_POINTS, &model));
     }
 }
 
-fn render_explosion(buf: &mut String, explosion: &Explosion, tick: u64) {
+fn render_explosion<M: MathBackend>(r: &mut dyn Renderer, explosion: &Explosion, tick: u64) {
     const EXPLOSION_RADIUS: f64 = 30.0;
     const EXPLOSION_PARTICLES: usize = 11;
     const EXPLOSION_PARTICLE_LENGTH: f64 = 10.0;
@@ -83,143 +112,77 @@ fn render_explosion(buf: &mut String, explosion: &Explosion, tick: u64) {
 
     for i in 0..EXPLOSION_PARTICLES {
         let a = explosion_da * (i as f64);
-        let dir = Vec2D::one().rotate(a);
-        let start = dir.scale(state * EXPLOSION_RADIUS) + explosion.pos;
-        let end = dir.scale(state * EXPLOSION_RADIUS + EXPLOSION_PARTICLE_LENGTH * (1.0 + state)) + explosion.pos;
-        write!(buf, "M {:.2} {:.2} L {:.2} {:.2}", start.x, start.y, end.x, end.y)
-            .expect("could not write string?");
+        let dir = M::unit(a);
+        let start = explosion.pos + dir * (state * EXPLOSION_RADIUS);
+        let end = explosion.pos + dir * (state * EXPLOSION_RADIUS + EXPLOSION_PARTICLE_LENGTH * (1.0 + state));
+        r.line(start, end);
     }
 }
 
-const VECTOR_DIGITS: &[&[Vec2D]] = &[
-    // 0
-    &[
-        Vec2D { x: 1.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 1.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-        Vec2D { x: 0.0, y: 2.0 },
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 1.0, y: 0.0 },
-    ],
-    // 1
-    &[
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 1.0, y: 0.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-    ],
-    // 2
-    &[
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 1.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 1.0 },
-        Vec2D { x: 0.0, y: 3.0 },
-        Vec2D { x: 2.0, y: 3.0 },
-    ],
-    // 3
-    &[
-        Vec2D { x: 0.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 0.0 },
-        Vec2D { x: 1.0, y: 1.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-        Vec2D { x: 0.0, y: 3.0 },
-    ],
-    // 4
-    &[
-        Vec2D { x: 1.0, y: 0.0 },
-        Vec2D { x: 0.0, y: 2.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 2.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 3.0 },
-    ],
-    // 5
-    &[
-        Vec2D { x: 2.0, y: 0.0 },
-        Vec2D { x: 0.0, y: 0.0 },
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 1.0, y: 1.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-        Vec2D { x: 0.0, y: 3.0 },
-    ],
-    // 6
-    &[
-        Vec2D { x: 2.0, y: 0.0 },
-        Vec2D { x: 1.0, y: 0.0 },
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 0.0, y: 2.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 1.0, y: 1.0 },
-        Vec2D { x: 0.0, y: 1.0 },
-    ],
-    // 7
-    &[
-        Vec2D { x: 0.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 0.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-    ],
-    // 8
-    &[
-        Vec2D { x: 0.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 0.0 },
-        Vec2D { x: 2.0, y: 1.0 },
-        Vec2D { x: 0.0, y: 2.0 },
-        Vec2D { x: 0.0, y: 3.0 },
-        Vec2D { x: 2.0, y: 3.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 0.0, y: 0.0 },
-    ],
-    // 9
-    &[
-        Vec2D { x: 0.0, y: 3.0 },
-        Vec2D { x: 1.0, y: 3.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-        Vec2D { x: 2.0, y: 1.0 },
-        Vec2D { x: 1.0, y: 0.0 },
-        Vec2D { x: 0.0, y: 1.0 },
-        Vec2D { x: 1.0, y: 2.0 },
-        Vec2D { x: 2.0, y: 2.0 },
-    ]
-];
+fn render_score(r: &mut dyn Renderer, score: u64, viewport: &Viewport) {
+    let digits = score.to_string();
+    let hud_scale = viewport.hud_scale();
+    let digit_scale = 10.0 * hud_scale;
+    let digit_step = 30.0 * hud_scale;
+    let (_, scene_size) = viewport.scene_rect();
+    // right aligned
+    let digit_rightmost = scene_size.x - 80.0 * hud_scale;
+    let left = digit_rightmost - ((digits.len() - 1) as f64) * digit_step;
+    r.text(Vec2D { x: left, y: -viewport.hud_height() * 0.5 }, digit_scale, &digits);
+}
 
-fn render_score(buf: &mut String, mut score: u64) {
-    let mut digits = Vec::new();
-    while score > 0 {
-        digits.push(score % 10);
-        score /= 10;
-    }
-    if digits.len() == 0 {
-        digits.push(0);
-    }
-    const DIGIT_SCALE: f64 = 10.0;
-    const DIGIT_STEP: f64 = -30.0;
-    const DIGIT_RIGHTMOST: f64 = 1200.0;
-    for (idx, d) in digits.iter().enumerate() {
-        let digit = VECTOR_DIGITS[*d as usize];
-        for (i, p) in digit.iter().enumerate() {
-            let p = Vec2D { x: p.x, y: p.y }.scale(DIGIT_SCALE)
-                    + Vec2D { x: DIGIT_RIGHTMOST + (idx as f64) * DIGIT_STEP, y: -DIGIT_SCALE * 5.0 };
-            let c = if i == 0 { 'M' } else { 'L' };
-            write!(buf, "{} {:.2} {:.2} ", c, p.x, p.y)
-                .expect("could not write string?");
-        }
+fn render_banner(r: &mut dyn Renderer, game: &Game) {
+    const DIGIT_SCALE: f64 = 30.0;
+    let number = match game.banner {
+        Some(number) => number,
+        None => return,
+    };
+    let digits = number.to_string();
+    // digits are 2 by 3 units, 3 units apart, centered on the field
+    let width = (digits.len() as f64) * 3.0 * DIGIT_SCALE - DIGIT_SCALE;
+    let center = game.config.field_size / 2.0;
+    let left = Vec2D { x: center.x - width * 0.5, y: center.y - DIGIT_SCALE * 1.5 };
+    r.text(left, DIGIT_SCALE, &digits);
+}
+
+/*
+ *     Drawn with the game's `MathBackend`, so that frames of a game in
+ * `MathMode::Deterministic` are the same on every target too, like its
+ * checksums.
+ */
+pub fn render_game(r: &mut dyn Renderer, game: &Game) {
+    match game.config.math {
+        MathMode::Native => render_game_with::<StdMath>(r, game),
+        MathMode::Deterministic => render_game_with::<SoftMath>(r, game),
     }
 }
 
-pub fn render_game(buf: &mut String, game: &Game) {
-    render_lives(buf, game.lives);
-    render_ship(buf, game);
+fn render_game_with<M: MathBackend>(r: &mut dyn Renderer, game: &Game) {
+    r.begin_layer(Style::Hud);
+    render_lives::<M>(r, game.lives, &game.viewport);
+    render_score(r, game.score, &game.viewport);
+
+    r.begin_layer(Style::Ship);
+    render_ship::<M>(r, game);
+
+    r.begin_layer(Style::Bullet);
     for bullet in game.bullets.iter() {
-        render_bullet(buf, bullet);
+        render_bullet(r, bullet);
     }
+
+    r.begin_layer(Style::Asteroid);
     for asteroid in game.asteroids.iter() {
-        render_asteroid(buf, asteroid);
+        render_asteroid::<M>(r, asteroid);
     }
+
+    r.begin_layer(Style::Explosion);
     for explosion in game.explosions.iter() {
-        render_explosion(buf, explosion, game.tick);
+        render_explosion::<M>(r, explosion, game.tick);
     }
-    render_score(buf, game.score);
+
+    r.begin_layer(Style::Overlay);
+    render_crosshair(r, game);
+    render_pause(r, game);
+    render_banner(r, game);
+    render_touch_controls::<M>(r, &game.touch);
 }
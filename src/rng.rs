@@ -1,10 +1,15 @@
 extern crate rand;
 
-pub use self::rand::{StdRng, Rng, SeedableRng};
+pub use self::rand::{Rng, SeedableRng};
+use self::rand::{Isaac64Rng};
+use crate::host::{host};
 
-extern "C" {
-    fn js_fill_rand(ptr: *mut u8, len: usize) -> usize;
-}
+/*
+ *     The game's generator, the same on every target: `StdRng` is a
+ * 32 bit generator on 32 bit targets like wasm, and a 64 bit one elsewhere,
+ * which is this one.
+ */
+pub type GameRng = Isaac64Rng;
 
 #[derive(Debug)]
 pub enum RNGSourceError {
@@ -14,23 +19,20 @@ pub enum RNGSourceError {
 }
 
 pub fn fill_random(buf: &mut [u8]) -> Result<(), RNGSourceError> {
-    let rv = unsafe { js_fill_rand(buf.as_mut_ptr(), buf.len()) };
-    match rv {
-        0 => Ok(()),
-        1 => Err(RNGSourceError::RangeError),
-        2 => Err(RNGSourceError::QuotaError),
-        _ => Err(RNGSourceError::UnknownError),
-    }
+    host().fill_random(buf)
+}
+
+/// The same seed gives the same numbers, on every platform.
+pub fn seeded_rng(seed: u64) -> GameRng {
+    GameRng::from_seed(&[seed >> 32, seed & 0xffff_ffff][..])
 }
 
-pub fn new_rng() -> Result<StdRng, RNGSourceError> {
+pub fn new_rng() -> Result<GameRng, RNGSourceError> {
     let mut seed = [0u8; 32];
     fill_random(&mut seed)?;
-    let seed = unsafe {
-        ::std::slice::from_raw_parts::<usize>(
-            seed.as_ptr() as *const usize,
-            32 / ::std::mem::size_of::<usize>(),
-        )
-    };
-    Ok(StdRng::from_seed(seed))
+    // the byte array isn't aligned for reading it as words
+    let seed: Vec<u64> = seed.chunks(8)
+        .map(|chunk| chunk.iter().fold(0, |word, &byte| (word << 8) | byte as u64))
+        .collect();
+    Ok(GameRng::from_seed(&seed[..]))
 }
@@ -1,5 +1,6 @@
-use ::math::{Vec2D};
-use ::game::{InputIndex, Inputs, Config};
+use crate::math::{Vec2D, wrap_angle};
+use crate::game::{Inputs, Config, AxisIndex};
+use crate::numeric::{MathBackend};
 
 #[derive(Debug)]
 pub struct Ship {
@@ -21,7 +22,16 @@ impl Ship {
         }
     }
 
-    pub fn tick(&mut self, inputs: &Inputs, config: &Config) {
+    /// Rotation input that turns the ship towards `target` without overshooting.
+    fn aim_rotation<M: MathBackend>(&self, target: Vec2D, config: &Config) -> f64 {
+        let diff = wrap_angle(M::angle(target - self.pos) - self.angle);
+        let wanted_speed = (diff * config.aim_gain)
+            .min(config.angular_limit).max(-config.angular_limit);
+        let accel = (wanted_speed - self.angular_speed) / (config.angular_accel * config.delta_t);
+        accel.clamp(-1.0, 1.0)
+    }
+
+    pub fn tick<M: MathBackend>(&mut self, inputs: &Inputs, config: &Config) {
         // drag
         let drag = self.speed.dot(self.speed) * config.drag;
         self.speed -= self.speed.scale(drag * config.delta_t);
@@ -30,21 +40,16 @@ impl Ship {
         self.angular_speed -= angular_drag * config.delta_t;
 
         // inputs
-        let accel_dir = match (inputs.is_down(InputIndex::Forward), inputs.is_down(InputIndex::Backward)) {
-            (true, false) => 1.0,
-            (false, true) => -1.0,
-            _ => 0.0,
-        };
+        let accel_dir = inputs.axis(AxisIndex::Thrust);
         if accel_dir != 0.0 {
             let accel = accel_dir * config.acceleration * config.delta_t;
-            let accel = Vec2D { x: accel, y: 0.0 }.rotate(self.angle);
+            let accel = M::rotate(Vec2D { x: accel, y: 0.0 }, self.angle);
             self.speed += accel;
         }
 
-        let rotate_dir = match (inputs.is_down(InputIndex::Left), inputs.is_down(InputIndex::Right)) {
-            (true, false) => -1.0,
-            (false, true) => 1.0,
-            _ => 0.0,
+        let rotate_dir = match inputs.aim() {
+            Some(target) if inputs.axis(AxisIndex::Rotate) == 0.0 => self.aim_rotation::<M>(target, config),
+            _ => inputs.axis(AxisIndex::Rotate),
         };
         if rotate_dir != 0.0 {
             let accel = rotate_dir * config.angular_accel * config.delta_t;
@@ -52,14 +57,14 @@ impl Ship {
         }
 
         // limiters
-        let speed = self.speed.len();
+        let speed = M::len(self.speed);
         if speed > config.speed_limit {
             self.speed = self.speed.scale(1.0 * config.speed_limit / speed);
         }
         self.angular_speed = self.angular_speed.min(config.angular_limit).max(-config.angular_limit);
 
         // constrain position
-        self.pos.clip(&config.field_size);
+        self.pos = M::wrap(self.pos, config.field_size);
 
         // integration step
         self.pos += self.speed.scale(config.delta_t);
@@ -0,0 +1,294 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use crate::game::{Game, GameState, Config, MathMode};
+use crate::keycode::{KeyCode, Modifiers};
+use crate::rng::{seeded_rng};
+
+/*
+ *     Headless simulation, for balance checks and regression tests.
+ * A seeded game is played by a script of key presses, without rendering
+ * and without a host event loop. The player doesn't wait: the next level
+ * and respawns are started as soon as the game allows it.
+ *
+ *     Scripts have one event per line, `#` starts a comment:
+ *
+ *     <when> <down|up> <KeyboardEvent.code>
+ *
+ * `when` is a tick number, fractions happen within the tick, or `@` and
+ * a time in milliseconds since the start, e.g. from a recorded input log:
+ *
+ *     0 down Space
+ *     30.5 up Space
+ *     @1000 down ArrowLeft
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptEvent {
+    // milliseconds since the start of the game
+    pub time: f64,
+    pub code: KeyCode,
+    pub down: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputScript {
+    pub events: Vec<ScriptEvent>,
+}
+
+impl InputScript {
+    /// Ticks are as long as in the default `Config`.
+    pub fn parse(script: &str) -> Result<InputScript, String> {
+        let delta_t = Config::new().delta_t;
+        let mut events = Vec::new();
+        for (number, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let event = InputScript::parse_line(line, delta_t)
+                .map_err(|err| format!("line {}: {}", number + 1, err))?;
+            events.push(event);
+        }
+        // events at the same time keep their order
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(InputScript { events: events })
+    }
+
+    fn parse_line(line: &str, delta_t: f64) -> Result<ScriptEvent, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 3 {
+            return Err(format!("expected `<when> <down|up> <key>`, got `{}`", line));
+        }
+        let time = match fields[0].strip_prefix('@') {
+            Some(ms) => ms.parse::<f64>().map_err(|_| format!("bad time `{}`", fields[0]))?,
+            None => fields[0].parse::<f64>().map_err(|_| format!("bad tick `{}`", fields[0]))? * delta_t * 1e3,
+        };
+        if !time.is_finite() || time < 0.0 {
+            return Err(format!("bad time `{}`", fields[0]));
+        }
+        let down = match fields[1] {
+            "down" => true,
+            "up" => false,
+            other => return Err(format!("expected `down` or `up`, got `{}`", other)),
+        };
+        let code = KeyCode::from_code(fields[2])
+            .ok_or_else(|| format!("unknown key code `{}`", fields[2]))?;
+        Ok(ScriptEvent { time: time, code: code, down: down })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub ticks: u64,
+    pub score: u64,
+    // levels are numbered from 1, which has 2 asteroids
+    pub level: usize,
+    pub deaths: u64,
+    pub game_over: bool,
+    // of the final state, see `Game::checksum`
+    pub checksum: u64,
+    pub tick_times: TickTimes,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TickTimes {
+    pub min: Duration,
+    pub mean: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl TickTimes {
+    fn new(mut times: Vec<Duration>) -> TickTimes {
+        if times.is_empty() {
+            return TickTimes::default();
+        }
+        times.sort();
+        let total: Duration = times.iter().sum();
+        TickTimes {
+            min: times[0],
+            mean: total / times.len() as u32,
+            // nearest rank
+            p99: times[(times.len() * 99).div_ceil(100) - 1],
+            max: times[times.len() - 1],
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let us = |d: Duration| d.as_secs_f64() * 1e6;
+        writeln!(f, "ticks: {}", self.ticks)?;
+        writeln!(f, "score: {}", self.score)?;
+        writeln!(f, "level: {}", self.level)?;
+        writeln!(f, "deaths: {}", self.deaths)?;
+        writeln!(f, "game over: {}", if self.game_over { "yes" } else { "no" })?;
+        writeln!(f, "checksum: {:016x}", self.checksum)?;
+        write!(f, "tick time: min {:.1}us, mean {:.1}us, p99 {:.1}us, max {:.1}us",
+               us(self.tick_times.min), us(self.tick_times.mean),
+               us(self.tick_times.p99), us(self.tick_times.max))
+    }
+}
+
+pub struct Simulation {
+    pub game: Game,
+    script: InputScript,
+    next_event: usize,
+    ticks: u64,
+    deaths: u64,
+    tick_times: Vec<Duration>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64, script: InputScript) -> Simulation {
+        Simulation::with_math(seed, script, MathMode::Native)
+    }
+
+    /// With `MathMode::Deterministic`, the checksum is the same on every target.
+    pub fn with_math(seed: u64, script: InputScript, math: MathMode) -> Simulation {
+        let mut config = Config::new();
+        config.math = math;
+        Simulation {
+            game: Game::with_rng(config, seeded_rng(seed)),
+            script: script,
+            next_event: 0,
+            ticks: 0,
+            deaths: 0,
+            tick_times: Vec::new(),
+        }
+    }
+
+    fn feed_input(&mut self, until: f64) {
+        while let Some(event) = self.script.events.get(self.next_event) {
+            if event.time > until {
+                break;
+            }
+            if event.down {
+                self.game.key_down(event.code, "", Modifiers::default(), false, event.time);
+            } else {
+                self.game.key_up(event.code, event.time);
+            }
+            self.next_event += 1;
+        }
+    }
+
+    /// Returns false once the game is over.
+    pub fn step(&mut self) -> bool {
+        let time = (self.ticks + 1) as f64 * self.game.config.delta_t * 1e3;
+        self.feed_input(time);
+        self.game.skip_menus();
+        let was_dead = self.game.ship.dead;
+
+        let start = Instant::now();
+        self.game.tick(time);
+        self.tick_times.push(start.elapsed());
+
+        self.ticks += 1;
+        if self.game.ship.dead && !was_dead {
+            self.deaths += 1;
+        }
+        self.game.game_state != GameState::GameOver
+    }
+
+    /// One line per tick, see `run`.
+    pub fn trace_line(&self) -> String {
+        let game = &self.game;
+        let state = match game.game_state {
+            GameState::Running => "running",
+            GameState::Paused => "paused",
+            GameState::Respawning => "respawning",
+            GameState::LevelCleared => "cleared",
+            GameState::GameOver => "over",
+        };
+        format!("{} {} score={} level={} lives={} ship=({:.2},{:.2},{:.4}) asteroids={} bullets={}",
+                self.ticks, state, game.score, game.level - 1, game.lives,
+                game.ship.pos.x, game.ship.pos.y, game.ship.angle,
+                game.asteroids.len(), game.bullets.len())
+    }
+
+    /// Runs up to `ticks` ticks, stopping early when the game is over,
+    /// and writes a trace line after every tick if `trace` is given.
+    pub fn run(&mut self, ticks: u64, mut trace: Option<&mut dyn Write>) -> io::Result<Summary> {
+        self.run_with(ticks, |sim| match trace.as_mut() {
+            Some(trace) => writeln!(trace, "{}", sim.trace_line()),
+            None => Ok(()),
+        })
+    }
+
+    /// Same as `run`, calling `after_tick` after every tick instead,
+    /// e.g. to record frames.
+    pub fn run_with<F>(&mut self, ticks: u64, mut after_tick: F) -> io::Result<Summary>
+        where F: FnMut(&Simulation) -> io::Result<()>
+    {
+        for _ in 0..ticks {
+            let running = self.step();
+            after_tick(self)?;
+            if !running {
+                break;
+            }
+        }
+        Ok(self.summary())
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            ticks: self.ticks,
+            score: self.game.score,
+            level: self.game.level - 1,
+            deaths: self.deaths,
+            game_over: self.game.game_state == GameState::GameOver,
+            checksum: self.game.checksum(),
+            tick_times: TickTimes::new(self.tick_times.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ticks_and_times() {
+        let script = InputScript::parse("# shoot\n60 down Space\n@500 up Space # early\n\n").unwrap();
+        assert_eq!(script.events, vec![
+            ScriptEvent { time: 500.0, code: KeyCode::Space, down: false },
+            ScriptEvent { time: 1000.0, code: KeyCode::Space, down: true },
+        ]);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        assert_eq!(InputScript::parse("1 down Space\n2 press Space"),
+                   Err("line 2: expected `down` or `up`, got `press`".to_string()));
+        assert!(InputScript::parse("1 down NoSuchKey").is_err());
+        assert!(InputScript::parse("-1 down Space").is_err());
+    }
+
+    #[test]
+    fn same_seed_and_script_play_the_same() {
+        let script = InputScript::parse(include_str!("../sim/example.txt")).unwrap();
+        let play = || {
+            let mut sim = Simulation::new(42, script.clone());
+            let mut trace = Vec::new();
+            let summary = sim.run(2000, Some(&mut trace)).unwrap();
+            (summary.score, summary.level, summary.deaths, summary.ticks, trace)
+        };
+        let first = play();
+        assert_eq!(first, play());
+        assert!(first.0 > 0, "the example script should score");
+    }
+
+    /*
+     *     Pins the checksum of a deterministic run, so that it only changes
+     * when the game rules or `SoftMath` do, which breaks replays recorded
+     * before. The same test run on wasm32, see the Readme, checks that wasm
+     * gets the same checksum as the native build.
+     */
+    #[test]
+    fn deterministic_checksum_is_stable() {
+        let script = InputScript::parse(include_str!("../sim/example.txt")).unwrap();
+        let mut sim = Simulation::with_math(42, script, MathMode::Deterministic);
+        let summary = sim.run(2000, None).unwrap();
+        assert_eq!(format!("{:016x}", summary.checksum), "41c4db042bc2a446");
+    }
+}
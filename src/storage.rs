@@ -0,0 +1,9 @@
+use crate::host::{host};
+
+pub fn load(key: &str) -> Option<String> {
+    host().storage_get(key)
+}
+
+pub fn save(key: &str, value: &str) {
+    host().storage_set(key, value);
+}
@@ -0,0 +1,200 @@
+use std::fmt::Write;
+use crate::game::{Game};
+use crate::math::{Vec2D};
+use crate::render::{Style, SvgLayers};
+use crate::render_path::{render_game};
+use crate::theme::{Theme};
+use crate::viewport::{Viewport};
+
+/*
+ *     Standalone SVG documents of what the game draws, for bug reports and
+ * golden tests. They look like the page does with the same `Theme`, with
+ * a `<path>` per layer, and show the whole scene at its natural size,
+ * clipped like `update_viewport` clips it, so they don't depend on the
+ * screen the game was played on.
+ *
+ *     A run is exported as one document, with the path data of every frame
+ * played by a SMIL `<animate>` per layer, which browsers play without any
+ * script.
+ */
+
+// `<animate>` values can't be empty, a lone move draws nothing
+const EMPTY_PATH: &str = "M0 0";
+
+fn frame_layers(game: &Game) -> SvgLayers {
+    let mut layers = SvgLayers::new();
+    render_game(&mut layers, game);
+    layers
+}
+
+fn path_data(layers: &SvgLayers, style: Style) -> &str {
+    match layers.path(style).trim_end() {
+        "" => EMPTY_PATH,
+        d => d,
+    }
+}
+
+fn rect_attributes(origin: Vec2D, size: Vec2D) -> String {
+    format!("x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"", origin.x, origin.y, size.x, size.y)
+}
+
+fn document_start(out: &mut String, viewport: &Viewport, theme: &Theme) {
+    let (origin, size) = viewport.scene_rect();
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">",
+             origin.x, origin.y, size.x, size.y, size.x, size.y)
+        .expect("could not write string?");
+    writeln!(out, "<clipPath id=\"scene-clip\"><rect {}/></clipPath>", rect_attributes(origin, size))
+        .expect("could not write string?");
+    writeln!(out, "<rect {} fill=\"{}\"/>", rect_attributes(origin, size), theme.background)
+        .expect("could not write string?");
+    writeln!(out, "<g clip-path=\"url(#scene-clip)\" fill=\"none\" stroke-linejoin=\"round\">")
+        .expect("could not write string?");
+}
+
+// the path element of a layer, left open for the caller to finish
+fn path_start(out: &mut String, theme: &Theme, style: Style, d: &str) {
+    let layer = theme.layer(style);
+    write!(out, "<path id=\"layer-{}\" stroke=\"{}\" stroke-width=\"{}\" stroke-opacity=\"{}\" d=\"{}\"",
+           style.name(), layer.stroke, layer.width, layer.opacity, d)
+        .expect("could not write string?");
+}
+
+const DOCUMENT_END: &str = "</g>\n</svg>\n";
+
+/// The frame the game would draw now, as an SVG document.
+pub fn frame_svg(game: &Game, theme: &Theme) -> String {
+    let layers = frame_layers(game);
+    let mut out = String::new();
+    document_start(&mut out, &game.viewport, theme);
+    for &style in Style::ALL.iter() {
+        path_start(&mut out, theme, style, path_data(&layers, style));
+        out.push_str("/>\n");
+    }
+    out.push_str(DOCUMENT_END);
+    out
+}
+
+/// Frames of a run, played back at a fixed rate in a single SVG document.
+pub struct SvgAnimation {
+    viewport: Viewport,
+    theme: Theme,
+    frame_ms: f64,
+    frames: Vec<SvgLayers>,
+}
+
+impl SvgAnimation {
+    /// Frames are shown `frame_ms` milliseconds each, the whole run in `viewport`.
+    pub fn new(viewport: &Viewport, theme: &Theme, frame_ms: f64) -> SvgAnimation {
+        SvgAnimation {
+            viewport: *viewport,
+            theme: *theme,
+            frame_ms: frame_ms,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn add_frame(&mut self, game: &Game) {
+        self.frames.push(frame_layers(game));
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Loops forever, discrete steps, so that every frame is shown as drawn.
+    pub fn to_svg(&self) -> String {
+        let mut out = String::new();
+        document_start(&mut out, &self.viewport, &self.theme);
+        let duration = self.frame_ms * (self.frames.len() as f64) / 1e3;
+        for &style in Style::ALL.iter() {
+            let first = self.frames.first().map_or(EMPTY_PATH, |layers| path_data(layers, style));
+            path_start(&mut out, &self.theme, style, first);
+            if self.frames.len() < 2 {
+                out.push_str("/>\n");
+                continue;
+            }
+            writeln!(out, ">\n<animate attributeName=\"d\" dur=\"{}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"", duration)
+                .expect("could not write string?");
+            let values: Vec<&str> = self.frames.iter().map(|layers| path_data(layers, style)).collect();
+            writeln!(out, "values=\"{}\"/>\n</path>", values.join(";\n")).expect("could not write string?");
+        }
+        out.push_str(DOCUMENT_END);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::{Path};
+    use crate::game::{MathMode};
+    use crate::render::{LAYER_COUNT};
+    use crate::theme;
+    use crate::sim::{InputScript, Simulation};
+
+    /*
+     *     Golden files are compared byte for byte, the simulation runs and
+     * draws with deterministic math so that they're the same on every
+     * machine. When the drawing changes on purpose,
+     * `UPDATE_GOLDEN=1 cargo test` rewrites them, and the diff shows up for
+     * review like any other change.
+     */
+    fn check_golden(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("sim/golden").join(name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, actual).expect("could not write golden file");
+            return;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("{}: {}, run with UPDATE_GOLDEN=1 to create it", path.display(), err));
+        assert!(expected == actual, "{} differs from the rendered output, run with UPDATE_GOLDEN=1 to update it",
+                path.display());
+    }
+
+    fn simulation() -> Simulation {
+        let script = InputScript::parse(include_str!("../sim/example.txt")).unwrap();
+        Simulation::with_math(42, script, MathMode::Deterministic)
+    }
+
+    #[test]
+    fn frame_matches_golden() {
+        let mut sim = simulation();
+        sim.run(600, None).unwrap();
+        let svg = frame_svg(&sim.game, &theme::CLASSIC);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 -100 "));
+        check_golden("seed42-tick600.svg", &svg);
+    }
+
+    #[test]
+    fn animation_matches_golden() {
+        let mut sim = simulation();
+        let mut animation = SvgAnimation::new(&sim.game.viewport, &theme::AMBER, 500.0);
+        sim.run_with(120, |sim| {
+            if sim.game.tick % 30 == 0 {
+                animation.add_frame(&sim.game);
+            }
+            Ok(())
+        }).unwrap();
+        assert_eq!(animation.len(), 4);
+        let svg = animation.to_svg();
+        assert_eq!(svg.matches("dur=\"2s\"").count(), LAYER_COUNT);
+        assert_eq!(svg.matches(';').count(), 3 * LAYER_COUNT);
+        assert!(svg.contains("<path id=\"layer-ship\" stroke=\"#ffb000\" stroke-width=\"3\""));
+        check_golden("seed42-ticks0-120.svg", &svg);
+    }
+
+    #[test]
+    fn single_frame_is_not_animated() {
+        let sim = simulation();
+        let mut animation = SvgAnimation::new(&sim.game.viewport, &theme::CLASSIC, 100.0);
+        assert_eq!(animation.to_svg().matches(" d=\"M0 0\"/>").count(), LAYER_COUNT);
+        animation.add_frame(&sim.game);
+        assert!(!animation.to_svg().contains("<animate"));
+    }
+}
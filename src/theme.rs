@@ -0,0 +1,102 @@
+use crate::render::{Style, LAYER_COUNT};
+
+/*
+ *     How the layers look, see `Style`. The host gets the styles with
+ * `svg_set_style`, and draws both the SVG paths and display lists with
+ * them, SVG exports use them too. Colors are CSS colors, widths are in
+ * field units, so lines get thicker with the viewport scale.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LayerStyle {
+    pub stroke: &'static str,
+    pub width: f64,
+    pub opacity: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: &'static str,
+    // in `Style` order
+    pub layers: [LayerStyle; LAYER_COUNT],
+}
+
+impl Theme {
+    pub fn layer(&self, style: Style) -> &LayerStyle {
+        &self.layers[style as usize]
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Theme> {
+        THEMES.iter().find(|theme| theme.name == name)
+    }
+}
+
+const fn stroke(stroke: &'static str, width: f64, opacity: f64) -> LayerStyle {
+    LayerStyle { stroke: stroke, width: width, opacity: opacity }
+}
+
+// white lines on black, as the game has always looked
+pub const CLASSIC: Theme = Theme {
+    name: "classic",
+    background: "black",
+    layers: [stroke("white", 2.5, 1.0); LAYER_COUNT],
+};
+
+pub const NEON: Theme = Theme {
+    name: "neon",
+    background: "#0a0318",
+    layers: [
+        stroke("#39ff14", 2.0, 0.9),
+        stroke("#00e5ff", 3.0, 1.0),
+        stroke("#ff2bd6", 3.0, 1.0),
+        stroke("#b967ff", 2.5, 0.9),
+        stroke("#ffe600", 2.0, 0.8),
+        stroke("#ffffff", 2.0, 0.5),
+    ],
+};
+
+// thick, fully opaque lines in colors that are told apart without hue
+pub const HIGH_CONTRAST: Theme = Theme {
+    name: "high-contrast",
+    background: "black",
+    layers: [
+        stroke("white", 3.0, 1.0),
+        stroke("yellow", 4.0, 1.0),
+        stroke("white", 4.0, 1.0),
+        stroke("cyan", 3.5, 1.0),
+        stroke("#ff8000", 3.0, 1.0),
+        stroke("white", 3.0, 1.0),
+    ],
+};
+
+// a single phosphor color, told apart by brightness
+pub const AMBER: Theme = Theme {
+    name: "amber",
+    background: "#140900",
+    layers: [
+        stroke("#ffb000", 2.0, 0.75),
+        stroke("#ffb000", 3.0, 1.0),
+        stroke("#ffcc40", 3.0, 1.0),
+        stroke("#ffb000", 2.5, 0.85),
+        stroke("#ff9000", 2.0, 0.7),
+        stroke("#ffb000", 2.0, 0.55),
+    ],
+};
+
+/// The themes the page offers, by number.
+pub const THEMES: &[Theme] = &[CLASSIC, NEON, HIGH_CONTRAST, AMBER];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_found_by_name() {
+        for theme in THEMES {
+            assert_eq!(Theme::by_name(theme.name), Some(theme));
+            assert!(theme.layers.iter().all(|layer| layer.width > 0.0 && layer.opacity > 0.0));
+        }
+        assert_eq!(Theme::by_name("amber").unwrap().layer(Style::Ship).stroke, "#ffb000");
+        assert!(Theme::by_name("sepia").is_none());
+    }
+}
@@ -1,9 +1,6 @@
 pub use std::time::Duration;
 
-extern "C" {
-    //static performance_time_origin: f64;
-    fn performance_now() -> f64;
-}
+use crate::host::{host};
 
 #[derive(Copy, Clone)]
 pub struct Instant {
@@ -12,10 +9,8 @@ pub struct Instant {
 
 impl Instant {
     pub fn now() -> Instant {
-        unsafe {
-            Instant {
-                now: performance_now(),
-            }
+        Instant {
+            now: host().now(),
         }
     }
     pub fn duration_since(&self, earlier: Instant) -> Duration {
@@ -25,6 +20,6 @@ impl Instant {
         Duration::new(secs, nano)
     }
     pub fn elapsed(&self) -> Duration {
-        Instant::now().duration_since(self.clone())
+        Instant::now().duration_since(*self)
     }
 }
@@ -0,0 +1,215 @@
+use crate::math::{Vec2D};
+use crate::input::{Inputs, InputIndex, AxisIndex, AxisSource};
+use crate::game::{Config};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TouchLayout {
+    // controls are drawn over the bottom corners of the field
+    Landscape,
+    // controls are drawn in a strip below the field
+    Portrait,
+}
+
+pub const STICK_RADIUS: f64 = 120.0;
+pub const FIRE_RADIUS: f64 = 90.0;
+// touches slightly outside of a control still grab it
+const GRAB_SCALE: f64 = 1.5;
+
+// the fire button presses whichever of these is in the active context
+const FIRE_INPUTS: &[InputIndex] = &[
+    InputIndex::Shoot,
+    InputIndex::Confirm,
+    InputIndex::Resume,
+];
+
+/*
+ *     Touches are tracked by their ids, so the stick and the fire button
+ * can be held with different fingers at the same time.
+ */
+pub struct TouchControls {
+    pub active: bool,
+    pub layout: TouchLayout,
+    field_size: Vec2D,
+    stick: Option<(u32, Vec2D)>,
+    fire: Option<(u32, InputIndex)>,
+}
+
+impl TouchControls {
+    pub fn new(field_size: Vec2D) -> TouchControls {
+        TouchControls {
+            active: false,
+            layout: TouchLayout::Landscape,
+            field_size: field_size,
+            stick: None,
+            fire: None,
+        }
+    }
+
+    pub fn stick_center(&self) -> Vec2D {
+        let field_size = self.field_size;
+        match self.layout {
+            TouchLayout::Landscape => Vec2D { x: 180.0, y: field_size.y - 160.0 },
+            TouchLayout::Portrait => Vec2D { x: field_size.x * 0.25, y: field_size.y + 300.0 },
+        }
+    }
+
+    pub fn fire_center(&self) -> Vec2D {
+        let field_size = self.field_size;
+        match self.layout {
+            TouchLayout::Landscape => Vec2D { x: field_size.x - 180.0, y: field_size.y - 160.0 },
+            TouchLayout::Portrait => Vec2D { x: field_size.x * 0.75, y: field_size.y + 300.0 },
+        }
+    }
+
+    /// Stick deflection, with length of at most 1.
+    pub fn stick(&self) -> Vec2D {
+        match self.stick {
+            Some((_, pos)) => {
+                let offset = (pos - self.stick_center()).scale(1.0 / STICK_RADIUS);
+                if offset.len() > 1.0 { offset.normalize() } else { offset }
+            },
+            None => Vec2D::zero(),
+        }
+    }
+
+    pub fn fire_held(&self) -> bool {
+        self.fire.is_some()
+    }
+
+    /// Forgets held touches, without releasing inputs.
+    pub fn forget_touches(&mut self) {
+        self.stick = None;
+        self.fire = None;
+    }
+
+    /// Forgets held touches and releases the stick.
+    pub fn release(&mut self, inputs: &mut Inputs, config: &Config) {
+        self.forget_touches();
+        self.update_stick(inputs, config);
+    }
+
+    pub fn touch_start(&mut self, id: u32, pos: Vec2D, inputs: &mut Inputs, config: &Config) {
+        self.active = true;
+        let stick_center = self.stick_center();
+        let fire_center = self.fire_center();
+        if self.stick.is_none() && (pos - stick_center).len() < STICK_RADIUS * GRAB_SCALE {
+            self.stick = Some((id, pos));
+            self.update_stick(inputs, config);
+        } else if self.fire.is_none() && (pos - fire_center).len() < FIRE_RADIUS * GRAB_SCALE {
+            let context = inputs.context();
+            if let Some(&input) = FIRE_INPUTS.iter().find(|input| input.context() == context) {
+                self.fire = Some((id, input));
+                inputs.action_down(input);
+            }
+        }
+    }
+
+    pub fn touch_move(&mut self, id: u32, pos: Vec2D, inputs: &mut Inputs, config: &Config) {
+        if let Some((stick_id, ref mut stick_pos)) = self.stick {
+            if stick_id == id {
+                *stick_pos = pos;
+            }
+        }
+        self.update_stick(inputs, config);
+    }
+
+    pub fn touch_end(&mut self, id: u32, inputs: &mut Inputs, config: &Config) {
+        if self.stick.is_some_and(|(stick_id, _)| stick_id == id) {
+            self.stick = None;
+            self.update_stick(inputs, config);
+        }
+        if let Some((fire_id, input)) = self.fire {
+            if fire_id == id {
+                self.fire = None;
+                inputs.action_up(input);
+            }
+        }
+    }
+
+    // pushing the stick up thrusts, sideways rotates
+    fn update_stick(&self, inputs: &mut Inputs, config: &Config) {
+        let stick = self.stick();
+        inputs.set_axis(AxisSource::Touch, AxisIndex::Rotate, stick.x, config);
+        inputs.set_axis(AxisSource::Touch, AxisIndex::Thrust, -stick.y, config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputContext};
+
+    fn controls() -> (TouchControls, Inputs, Config) {
+        let config = Config::new();
+        (TouchControls::new(config.field_size), Inputs::new(), config)
+    }
+
+    #[test]
+    fn stick_sets_axes() {
+        let (mut touch, mut inputs, config) = controls();
+        let center = touch.stick_center();
+        touch.touch_start(1, center, &mut inputs, &config);
+        assert!(touch.active);
+        assert_eq!(inputs.axis(AxisIndex::Rotate), 0.0);
+        // up thrusts, and dragging past the edge is full deflection
+        touch.touch_move(1, center + Vec2D { x: 0.0, y: -3.0 * STICK_RADIUS }, &mut inputs, &config);
+        assert_eq!(touch.stick(), Vec2D { x: 0.0, y: -1.0 });
+        assert_eq!(inputs.axis(AxisIndex::Thrust), 1.0);
+        touch.touch_move(1, center + Vec2D { x: STICK_RADIUS, y: 0.0 }, &mut inputs, &config);
+        assert_eq!(inputs.axis(AxisIndex::Rotate), 1.0);
+        assert_eq!(inputs.axis(AxisIndex::Thrust), 0.0);
+        touch.touch_end(1, &mut inputs, &config);
+        assert_eq!(inputs.axis(AxisIndex::Rotate), 0.0);
+    }
+
+    #[test]
+    fn stick_and_fire_are_held_by_different_fingers() {
+        let (mut touch, mut inputs, config) = controls();
+        let stick_center = touch.stick_center();
+        touch.touch_start(1, stick_center, &mut inputs, &config);
+        touch.touch_start(2, touch.fire_center(), &mut inputs, &config);
+        assert!(touch.fire_held());
+        assert!(inputs.is_down(InputIndex::Shoot));
+        // moving the fire finger doesn't move the stick
+        touch.touch_move(2, stick_center + Vec2D { x: STICK_RADIUS, y: 0.0 }, &mut inputs, &config);
+        assert_eq!(touch.stick(), Vec2D::zero());
+        touch.touch_end(1, &mut inputs, &config);
+        assert!(inputs.is_down(InputIndex::Shoot));
+        touch.touch_end(2, &mut inputs, &config);
+        assert!(!touch.fire_held());
+        assert!(inputs.just_released(InputIndex::Shoot));
+    }
+
+    #[test]
+    fn touches_away_from_the_controls_are_ignored() {
+        let (mut touch, mut inputs, config) = controls();
+        touch.touch_start(1, Vec2D { x: 640.0, y: 100.0 }, &mut inputs, &config);
+        assert!(!touch.fire_held());
+        assert_eq!(touch.stick(), Vec2D::zero());
+        // slightly outside of a control still grabs it
+        let fire_center = touch.fire_center();
+        touch.touch_start(2, fire_center + Vec2D { x: FIRE_RADIUS * 1.2, y: 0.0 }, &mut inputs, &config);
+        assert!(touch.fire_held());
+    }
+
+    #[test]
+    fn fire_presses_the_input_of_the_active_context() {
+        let (mut touch, mut inputs, config) = controls();
+        inputs.set_context(InputContext::Menu);
+        touch.touch_start(1, touch.fire_center(), &mut inputs, &config);
+        assert!(inputs.just_pressed(InputIndex::Confirm));
+        touch.touch_end(1, &mut inputs, &config);
+        inputs.set_context(InputContext::Paused);
+        touch.touch_start(2, touch.fire_center(), &mut inputs, &config);
+        assert!(inputs.just_pressed(InputIndex::Resume));
+    }
+
+    #[test]
+    fn portrait_controls_are_below_the_field() {
+        let (mut touch, _, config) = controls();
+        touch.layout = TouchLayout::Portrait;
+        assert!(touch.stick_center().y > config.field_size.y);
+        assert!(touch.fire_center().y > config.field_size.y);
+        assert!(touch.stick_center().x < touch.fire_center().x);
+    }
+}
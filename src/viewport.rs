@@ -0,0 +1,167 @@
+use crate::math::{Vec2D};
+use crate::touch::{TouchLayout};
+
+/*
+ *     The scene is the field, with the HUD strip above it, and in portrait
+ * a strip for touch controls below it. It's scaled to fit the screen,
+ * keeping the aspect ratio, and centered, leaving letterbox bars on the sides
+ * that don't fit. The renderer draws in field coordinates, and the viewport
+ * transform is applied to the whole drawing.
+ */
+const HUD_HEIGHT: f64 = 100.0;
+// portrait screens are narrow, so the HUD is drawn larger to stay readable
+const PORTRAIT_HUD_SCALE: f64 = 2.0;
+const TOUCH_STRIP_HEIGHT: f64 = 600.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Viewport {
+    pub layout: TouchLayout,
+    // screen pixels per field unit
+    pub scale: f64,
+    // screen position of the field origin
+    pub offset: Vec2D,
+    field_size: Vec2D,
+}
+
+impl Viewport {
+    /// A viewport showing the landscape scene at its natural size.
+    pub fn new(field_size: Vec2D) -> Viewport {
+        let scene = Viewport::scene_size_for(field_size, TouchLayout::Landscape);
+        Viewport::fit(field_size, scene.x, scene.y, 1.0)
+    }
+
+    /// `width` and `height` are in CSS pixels, `dpr` is device pixels per CSS pixel.
+    pub fn fit(field_size: Vec2D, width: f64, height: f64, dpr: f64) -> Viewport {
+        let layout = if height > width { TouchLayout::Portrait } else { TouchLayout::Landscape };
+        let scene = Viewport::scene_size_for(field_size, layout);
+        let scale = (width / scene.x).min(height / scene.y);
+        let hud_height = Viewport::hud_height_for(layout);
+        let offset = Vec2D {
+            x: (width - scene.x * scale) * 0.5,
+            y: (height - scene.y * scale) * 0.5 + hud_height * scale,
+        };
+        // keep lines on the same device pixels as the screen is resized
+        let dpr = if dpr > 0.0 { dpr } else { 1.0 };
+        let offset = Vec2D {
+            x: (offset.x * dpr).round() / dpr,
+            y: (offset.y * dpr).round() / dpr,
+        };
+        Viewport {
+            layout: layout,
+            scale: scale,
+            offset: offset,
+            field_size: field_size,
+        }
+    }
+
+    fn hud_height_for(layout: TouchLayout) -> f64 {
+        HUD_HEIGHT * match layout {
+            TouchLayout::Landscape => 1.0,
+            TouchLayout::Portrait => PORTRAIT_HUD_SCALE,
+        }
+    }
+
+    fn scene_size_for(field_size: Vec2D, layout: TouchLayout) -> Vec2D {
+        let below = match layout {
+            TouchLayout::Landscape => 0.0,
+            TouchLayout::Portrait => TOUCH_STRIP_HEIGHT,
+        };
+        Vec2D {
+            x: field_size.x,
+            y: Viewport::hud_height_for(layout) + field_size.y + below,
+        }
+    }
+
+    /// The HUD strip is drawn above the field, at negative y.
+    pub fn hud_height(&self) -> f64 {
+        Viewport::hud_height_for(self.layout)
+    }
+
+    pub fn hud_scale(&self) -> f64 {
+        self.hud_height() / HUD_HEIGHT
+    }
+
+    /// Top left corner and size of the scene, in field coordinates.
+    pub fn scene_rect(&self) -> (Vec2D, Vec2D) {
+        let origin = Vec2D { x: 0.0, y: -self.hud_height() };
+        (origin, Viewport::scene_size_for(self.field_size, self.layout))
+    }
+
+    /// Converts screen coordinates in CSS pixels into field coordinates.
+    pub fn screen_to_field(&self, x: i32, y: i32) -> Vec2D {
+        let screen = Vec2D { x: f64::from(x), y: f64::from(y) };
+        (screen - self.offset).scale(1.0 / self.scale)
+    }
+
+    /// SVG transform from field coordinates to the screen.
+    pub fn svg_transform(&self) -> String {
+        format!("translate({:.3} {:.3}) scale({:.5})", self.offset.x, self.offset.y, self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELD: Vec2D = Vec2D { x: 1280.0, y: 720.0 };
+
+    // field coordinates to screen, the way `svg_transform` maps them
+    fn field_to_screen(viewport: &Viewport, pos: Vec2D) -> Vec2D {
+        pos.scale(viewport.scale) + viewport.offset
+    }
+
+    #[test]
+    fn wider_screens_are_letterboxed_on_the_sides() {
+        let viewport = Viewport::fit(FIELD, 2000.0, 820.0, 1.0);
+        assert_eq!(viewport.layout, TouchLayout::Landscape);
+        assert_eq!(viewport.scale, 1.0);
+        assert_eq!(viewport.offset, Vec2D { x: 360.0, y: 100.0 });
+        assert_eq!(viewport.svg_transform(), "translate(360.000 100.000) scale(1.00000)");
+    }
+
+    #[test]
+    fn taller_screens_are_letterboxed_above_and_below() {
+        let viewport = Viewport::fit(FIELD, 640.0, 600.0, 1.0);
+        assert_eq!(viewport.layout, TouchLayout::Landscape);
+        assert_eq!(viewport.scale, 0.5);
+        // 95 pixels of bars above, then the HUD
+        assert_eq!(viewport.offset, Vec2D { x: 0.0, y: 145.0 });
+    }
+
+    #[test]
+    fn portrait_screens_fit_the_touch_strip() {
+        let viewport = Viewport::fit(FIELD, 720.0, 1600.0, 1.0);
+        assert_eq!(viewport.layout, TouchLayout::Portrait);
+        assert_eq!(viewport.hud_height(), 200.0);
+        let (origin, size) = viewport.scene_rect();
+        assert_eq!(origin, Vec2D { x: 0.0, y: -200.0 });
+        assert_eq!(size, Vec2D { x: 1280.0, y: 1520.0 });
+        assert_eq!(viewport.scale, 0.5625);
+        assert_eq!(viewport.offset, Vec2D { x: 0.0, y: 485.0 });
+    }
+
+    #[test]
+    fn offset_snaps_to_device_pixels() {
+        let viewport = Viewport::fit(FIELD, 1280.0, 821.0, 2.0);
+        assert_eq!(viewport.offset, Vec2D { x: 0.0, y: 100.5 });
+        let viewport = Viewport::fit(FIELD, 1280.0, 821.0, 1.0);
+        // halves round away from zero
+        assert_eq!(viewport.offset, Vec2D { x: 0.0, y: 101.0 });
+    }
+
+    #[test]
+    fn screen_to_field_inverts_the_transform() {
+        for &(width, height) in [(2000.0, 820.0), (640.0, 600.0), (720.0, 1600.0), (1000.0, 1000.0)].iter() {
+            let viewport = Viewport::fit(FIELD, width, height, 1.0);
+            for &(x, y) in [(0, 0), (400, 300), (719, 599)].iter() {
+                let field = viewport.screen_to_field(x, y);
+                let screen = field_to_screen(&viewport, field);
+                assert!((screen.x - f64::from(x)).abs() < 1e-9 && (screen.y - f64::from(y)).abs() < 1e-9,
+                        "{}x{}: ({}, {}) came back as {:?}", width, height, x, y, screen);
+            }
+        }
+        let viewport = Viewport::fit(FIELD, 2000.0, 820.0, 1.0);
+        assert_eq!(viewport.screen_to_field(360, 100), Vec2D::zero());
+        assert_eq!(viewport.screen_to_field(1640, 820), FIELD);
+    }
+}
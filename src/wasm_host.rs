@@ -0,0 +1,136 @@
+use crate::host::{Host};
+use crate::rng::{RNGSourceError};
+
+extern "C" {
+    fn alert(n: f64);
+    fn puts(ptr: *const u8, len: usize);
+    fn performance_now() -> f64;
+    fn js_fill_rand(ptr: *mut u8, len: usize) -> usize;
+
+    fn svg_set_path(layer: u32, ptr: *const u8, len: usize);
+    fn svg_set_style(layer: u32, ptr: *const u8, len: usize, width: f64, opacity: f64);
+    fn svg_set_background(ptr: *const u8, len: usize);
+    fn svg_set_transform(ptr: *const u8, len: usize);
+    fn svg_set_clip(x: f64, y: f64, width: f64, height: f64);
+    fn display_list_draw(ptr: *const u8, len: usize);
+
+    fn storage_get(key_ptr: *const u8, key_len: usize, len: *mut usize) -> *mut u8;
+    fn storage_set(key_ptr: *const u8, key_len: usize, val_ptr: *const u8, val_len: usize);
+
+    fn gamepad_rumble(strong: f64, weak: f64, duration_ms: f64);
+
+    fn event_loop_new() -> u32;
+    fn event_loop_raf(id: u32);
+    fn event_loop_shutdown(id: u32) -> bool;
+    fn event_loop_set_timer(id: u32, delay_ms: f64, repeat: bool) -> u32;
+    fn event_loop_clear_timer(id: u32, handle: u32) -> bool;
+    fn event_loop_subscribe(id: u32, categories: u32);
+    fn event_loop_unsubscribe(id: u32, categories: u32);
+    fn event_loop_set_target(id: u32, selector_ptr: *const u8, selector_len: usize) -> bool;
+}
+
+/// The browser, through the functions demo.js provides.
+pub struct WasmHost;
+
+impl Host for WasmHost {
+    fn now(&self) -> f64 {
+        unsafe { performance_now() }
+    }
+
+    fn fill_random(&self, buf: &mut [u8]) -> Result<(), RNGSourceError> {
+        let rv = unsafe { js_fill_rand(buf.as_mut_ptr(), buf.len()) };
+        match rv {
+            0 => Ok(()),
+            1 => Err(RNGSourceError::RangeError),
+            2 => Err(RNGSourceError::QuotaError),
+            _ => Err(RNGSourceError::UnknownError),
+        }
+    }
+
+    fn puts(&self, s: &str) {
+        unsafe { puts(s.as_ptr(), s.len()) };
+    }
+
+    fn alert(&self, n: f64) {
+        unsafe { alert(n) };
+    }
+
+    fn svg_set_path(&self, layer: u32, path: &str) {
+        unsafe { svg_set_path(layer, path.as_ptr(), path.len()) };
+    }
+
+    fn svg_set_style(&self, layer: u32, stroke: &str, width: f64, opacity: f64) {
+        unsafe { svg_set_style(layer, stroke.as_ptr(), stroke.len(), width, opacity) };
+    }
+
+    fn svg_set_background(&self, color: &str) {
+        unsafe { svg_set_background(color.as_ptr(), color.len()) };
+    }
+
+    fn svg_set_transform(&self, transform: &str) {
+        unsafe { svg_set_transform(transform.as_ptr(), transform.len()) };
+    }
+
+    fn svg_set_clip(&self, x: f64, y: f64, width: f64, height: f64) {
+        unsafe { svg_set_clip(x, y, width, height) };
+    }
+
+    /// The host reads the list straight out of our memory, during the call.
+    fn draw_display_list(&self, list: &[u8]) {
+        unsafe { display_list_draw(list.as_ptr(), list.len()) };
+    }
+
+    /*
+     *     The host allocates the returned value with `alloc` and hands
+     * ownership of it to us, so it's freed as a regular `Vec`.
+     */
+    fn storage_get(&self, key: &str) -> Option<String> {
+        let mut len = 0;
+        let ptr = unsafe { storage_get(key.as_ptr(), key.len(), &mut len) };
+        if ptr.is_null() {
+            return None;
+        }
+        let buf = unsafe { Vec::from_raw_parts(ptr, len, len) };
+        String::from_utf8(buf).ok()
+    }
+
+    fn storage_set(&self, key: &str, value: &str) {
+        unsafe { storage_set(key.as_ptr(), key.len(), value.as_ptr(), value.len()) };
+    }
+
+    fn gamepad_rumble(&self, strong: f64, weak: f64, duration_ms: f64) {
+        unsafe { gamepad_rumble(strong, weak, duration_ms) };
+    }
+
+    fn event_loop_new(&self) -> u32 {
+        unsafe { event_loop_new() }
+    }
+
+    fn event_loop_raf(&self, id: u32) {
+        unsafe { event_loop_raf(id) };
+    }
+
+    fn event_loop_shutdown(&self, id: u32) -> bool {
+        unsafe { event_loop_shutdown(id) }
+    }
+
+    fn event_loop_set_timer(&self, id: u32, delay_ms: f64, repeat: bool) -> u32 {
+        unsafe { event_loop_set_timer(id, delay_ms, repeat) }
+    }
+
+    fn event_loop_clear_timer(&self, id: u32, handle: u32) -> bool {
+        unsafe { event_loop_clear_timer(id, handle) }
+    }
+
+    fn event_loop_subscribe(&self, id: u32, categories: u32) {
+        unsafe { event_loop_subscribe(id, categories) };
+    }
+
+    fn event_loop_unsubscribe(&self, id: u32, categories: u32) {
+        unsafe { event_loop_unsubscribe(id, categories) };
+    }
+
+    fn event_loop_set_target(&self, id: u32, selector: &str) -> bool {
+        unsafe { event_loop_set_target(id, selector.as_ptr(), selector.len()) }
+    }
+}